@@ -7,11 +7,17 @@ fn main() {
     // in Linux
 
     match delete(SERVICE_NAME) {
-        Ok(_) => {
-            println!("Service '{}' successfully deleted", SERVICE_NAME)
+        Ok(report) => {
+            for step in report.steps.iter() {
+                println!("{}: {:?}", step.name, step.result);
+            }
+
+            if report.is_success() {
+                println!("Service '{}' successfully deleted", SERVICE_NAME)
+            }
         }
         Err(err) => {
-            println!("Could not delete service '{}', {}", SERVICE_NAME, err.to_string())
+            println!("Could not delete service '{}', {}", SERVICE_NAME, err)
         }
     }
 
@@ -16,14 +16,19 @@ fn main() {
         description: "My cross platform service".into(),
         exec_path: path,
         auto_start: false,
+        ..Default::default()
     };
 
     match install(service_info) {
-        Ok(_) => {
+        Ok(changes) => {
             println!("Service '{}' installed successfully", SERVICE_NAME);
+
+            for change in changes {
+                println!("{} {} {}", change.change_type, change.file_name, change.destination);
+            }
         }
         Err(err) => {
-            println!("Could not install service '{}'. {}", SERVICE_NAME, err.to_string());
+            println!("Could not install service '{}'. {}", SERVICE_NAME, err);
         }
     }
 }
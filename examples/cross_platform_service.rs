@@ -1,32 +1,28 @@
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::io::Error;
 use std::time::Duration;
 
-use cross_platform_service::service::start_service;
+use cross_platform_service::service::{ServiceContext, start_service_with};
 
 const SERVICE_NAME: &str = "MyCrPlSVC";
 
 fn main() {
-    // By calling service_start, "service_main" will be called as the service function,
+    // By calling start_service_with, "service_main" will be called as the service function,
     // and current function will be continued after returning from service_main
-    start_service(SERVICE_NAME, service_main);
+    start_service_with(SERVICE_NAME, service_main);
 }
 
-fn service_main(running: Arc<AtomicBool>) {
-    // The following code will run service for a minute and check if service stopped by operating-
-    // system every 100 milli-seconds
+fn service_main(context: ServiceContext) -> Result<(), Error> {
+    // The following code will run service for a minute or until service stopped by operating
+    // system
     // Returning from this function will notify operating system that current service has
-    // been stopped
-    for _ in 0..600 {
-        if !running.load(Ordering::Relaxed) {
-            // Running value will be set to false when SIGTERM signal received on Linux or stop
-            // control sent by Windows service manager
+    // been stopped, returning an error will report the service as failed
+    let shutdown = context.shutdown_token();
 
-            return;
-        }
-
-        std::thread::sleep(Duration::from_millis(100));
+    if shutdown.wait_timeout(Duration::from_secs(60)) {
+        // Token will be cancelled when SIGTERM signal received on Linux or stop control sent by
+        // Windows service manager
+        // Write stopping service codes here
     }
-}
\ No newline at end of file
+
+    Ok(())
+}
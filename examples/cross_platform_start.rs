@@ -8,7 +8,7 @@ fn main() {
             println!("Service '{}' successfully started", SERVICE_NAME)
         }
         Err(err) => {
-            println!("Could not start service '{}', {}", SERVICE_NAME, err.to_string())
+            println!("Could not start service '{}', {}", SERVICE_NAME, err)
         }
     }
 }
\ No newline at end of file
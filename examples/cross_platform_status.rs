@@ -0,0 +1,21 @@
+use cross_platform_service::service_manager::status;
+
+const SERVICE_NAME: &str = "MyCrPlSVC";
+
+fn main() {
+    match status(SERVICE_NAME) {
+        Ok(value) => {
+            println!("Service '{}' is {:?}", SERVICE_NAME, value.state);
+
+            if let Some(pid) = value.main_pid {
+                println!("Main PID: {}", pid);
+            }
+            if let Some(code) = value.exit_code {
+                println!("Exit code: {}", code);
+            }
+        }
+        Err(err) => {
+            println!("Could not get status of service '{}', {}", SERVICE_NAME, err)
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use cross_platform_service::unix::services::install_string;
-use std::io::Error;
 use std::env::current_exe;
 
 const SERVICE_NAME: &str = "MyUnixSVC.service";
@@ -23,7 +22,7 @@ ExecStart={}", path.to_str().unwrap());
             println!("Service '{}' installed successfully", SERVICE_NAME);
         }
         Err(err) => {
-            println!("Service '{}' could not be installed. {}", SERVICE_NAME, err.to_string());
+            println!("Service '{}' could not be installed. {}", SERVICE_NAME, err);
         }
     }
 }
@@ -8,7 +8,7 @@ fn main() {
             println!("Service '{}' deleted successfully", SERVICE_NAME);
         }
         Err(err) => {
-            println!("Could not delete service '{}', {}", SERVICE_NAME, err.to_string());
+            println!("Could not delete service '{}', {}", SERVICE_NAME, err);
         }
     }
 }
\ No newline at end of file
@@ -1,6 +1,5 @@
 use cross_platform_service::unix::services::{start_unit, Mode};
 use std::time::Duration;
-use std::io::Error;
 
 const SERVICE_NAME: &str = "MyUnixSVC.service";
 
@@ -12,7 +11,7 @@ fn main() {
             println!("Service '{}' started successfully", SERVICE_NAME);
         }
         Err(err) => {
-            println!("Could not start service '{}', {}", SERVICE_NAME, err.to_string());
+            println!("Could not start service '{}', {}", SERVICE_NAME, err);
         }
     }
 }
\ No newline at end of file
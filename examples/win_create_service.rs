@@ -45,7 +45,7 @@ fn main() {
             println!("Service created successfully");
         }
         Err(err) => {
-            println!("Could not create service. {}", err.to_string());
+            println!("Could not create service. {}", err);
         }
     }
 }
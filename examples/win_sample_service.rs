@@ -41,7 +41,7 @@ fn main() {
             // Service run successfully
         }
         Err(err) => {
-            println!("Could not start service. {}", err.to_string())
+            println!("Could not start service. {}", err)
         }
     };
 }
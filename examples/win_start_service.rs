@@ -15,7 +15,7 @@ fn main() {
                 println!("Service '{}' was successfully started", SERVICE_NAME);
             }
             Err(err) => {
-                println!("Could not start service '{}', {}", SERVICE_NAME, err.to_string());
+                println!("Could not start service '{}', {}", SERVICE_NAME, err);
             }
         }
     }
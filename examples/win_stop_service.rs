@@ -20,7 +20,7 @@ fn main() {
                 println!("Service '{}' successfully stopped", SERVICE_NAME);
             }
             Err(err) => {
-                println!("Could not stop service. {}", err.to_string());
+                println!("Could not stop service. {}", err);
             }
         };
     }
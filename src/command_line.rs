@@ -0,0 +1,164 @@
+/// Builds a command line for binary path of a service from specified program and arguments
+///
+/// Program path is enclosed in double quotes when it contains whitespace, so Windows does not
+/// look for an executable at a shorter path. Arguments are quoted by [quote_argument], so
+/// *CommandLineToArgvW* splits the command line into the same arguments. Available on every
+/// platform, so Windows command lines can be built and checked anywhere.
+///
+/// ```
+/// use cross_platform_service::command_line::quote_command_line;
+///
+/// assert_eq!(quote_command_line(r"C:\Program Files\My App\app.exe", &["--name", "My App", r#"a\"b"#]),
+///            r#""C:\Program Files\My App\app.exe" --name "My App" "a\\\"b""#);
+/// ```
+pub fn quote_command_line(program: &str, args: &[&str]) -> String {
+    let mut command_line = if program.contains([' ', '\t']) {
+        format!("\"{}\"", program)
+    } else {
+        program.to_string()
+    };
+
+    for arg in args {
+        command_line.push(' ');
+        command_line.push_str(quote_argument(arg).as_str());
+    }
+
+    command_line
+}
+
+/// Quotes an argument of a command line, following *CommandLineToArgvW* rules
+///
+/// Arguments which are empty or contain whitespace or quotes are enclosed in double quotes.
+/// Quotes are escaped by backslash, and backslashes are doubled only when they are followed by a
+/// quote.
+pub fn quote_argument(arg: &str) -> String {
+    let needs_quote = arg.is_empty() || arg.contains([' ', '\t', '\n', '\x0b', '"']);
+
+    if !needs_quote {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    let mut backslashes = 0;
+
+    quoted.push('"');
+
+    for c in arg.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                continue;
+            }
+            '"' => quoted.push_str("\\".repeat(backslashes * 2 + 1).as_str()),
+            _ => quoted.push_str("\\".repeat(backslashes).as_str()),
+        }
+
+        backslashes = 0;
+        quoted.push(c);
+    }
+
+    quoted.push_str("\\".repeat(backslashes * 2).as_str());
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits arguments of a command line as *CommandLineToArgvW* does after the program name
+    fn split_arguments(command_line: &str) -> Vec<String> {
+        let mut args = vec![];
+        let mut chars = command_line.chars().peekable();
+
+        loop {
+            while chars.peek().is_some_and(|it| *it == ' ' || *it == '\t') {
+                chars.next();
+            }
+
+            if chars.peek().is_none() {
+                return args;
+            }
+
+            let mut arg = String::new();
+            let mut quoted = false;
+            let mut backslashes = 0;
+
+            while let Some(c) = chars.peek().copied() {
+                if !quoted && (c == ' ' || c == '\t') {
+                    break;
+                }
+
+                chars.next();
+
+                match c {
+                    '\\' => backslashes += 1,
+                    '"' => {
+                        arg.push_str("\\".repeat(backslashes / 2).as_str());
+
+                        if backslashes % 2 == 1 {
+                            arg.push('"');
+                        } else {
+                            quoted = !quoted;
+                        }
+
+                        backslashes = 0;
+                    }
+                    _ => {
+                        arg.push_str("\\".repeat(backslashes).as_str());
+                        arg.push(c);
+                        backslashes = 0;
+                    }
+                }
+            }
+
+            arg.push_str("\\".repeat(backslashes).as_str());
+            args.push(arg);
+        }
+    }
+
+    #[test]
+    fn keeps_plain_arguments() {
+        assert_eq!(quote_argument("--verbose"), "--verbose");
+        assert_eq!(quote_argument(r"C:\data\"), r"C:\data\");
+        assert_eq!(quote_argument("%PATH%"), "%PATH%");
+    }
+
+    #[test]
+    fn quotes_empty_arguments_and_whitespace() {
+        assert_eq!(quote_argument(""), r#""""#);
+        assert_eq!(quote_argument("My App"), r#""My App""#);
+        assert_eq!(quote_argument("a\tb"), "\"a\tb\"");
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes_before_quotes() {
+        assert_eq!(quote_argument(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_argument(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(quote_argument(r#"a\\"b"#), r#""a\\\\\"b""#);
+        assert_eq!(quote_argument(r"a b\c"), r#""a b\c""#);
+    }
+
+    #[test]
+    fn doubles_trailing_backslashes() {
+        assert_eq!(quote_argument(r"C:\My Data\"), r#""C:\My Data\\""#);
+        assert_eq!(quote_argument(r"C:\My Data\\"), r#""C:\My Data\\\\""#);
+    }
+
+    #[test]
+    fn quotes_program_with_whitespace_only() {
+        assert_eq!(quote_command_line(r"C:\app.exe", &[]), r"C:\app.exe");
+        assert_eq!(quote_command_line(r"C:\My App\app.exe", &["", "x"]), r#""C:\My App\app.exe" "" x"#);
+    }
+
+    #[test]
+    fn round_trips_through_command_line_to_argv() {
+        let args = ["", " ", "plain", "My App", r#"say "hi""#, r#"a\"b"#, r#"a\\"b"#, r"C:\My Data\",
+                    r"C:\My Data\\", r"\\server\share", "%PATH%", "$HOME", "\"", "\\", "a\tb"];
+        let command_line = quote_command_line(r"C:\My App\app.exe", &args);
+
+        assert!(command_line.starts_with(r#""C:\My App\app.exe" "#));
+        assert_eq!(split_arguments(&command_line[r#""C:\My App\app.exe""#.len()..]), args);
+    }
+}
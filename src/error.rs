@@ -0,0 +1,168 @@
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+
+/// Source error of a [ServiceError]
+pub type ErrorSource = Box<dyn StdError + Send + Sync>;
+
+/// Provides errors of service management operations
+///
+/// Errors of service manager (D-Bus errors on Linux, Windows API errors on Windows) and OS errors
+/// are mapped to stable variants, and the original error is kept as [source](StdError::source).
+/// `ServiceError` converts into [std::io::Error], so it can be propagated by `?` from functions
+/// returning `io::Error`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServiceError {
+    /// Service or unit does not exist
+    NotFound(ErrorSource),
+    /// Caller does not have enough privileges to perform the operation
+    AccessDenied(ErrorSource),
+    /// Service or unit file already exists
+    AlreadyExists(ErrorSource),
+    /// Service manager did not respond in time
+    Timeout(ErrorSource),
+    /// Invalid service name, unit content or argument
+    InvalidArgument(ErrorSource),
+    /// Service manager is not available, e.g. system bus is not running
+    Unavailable(ErrorSource),
+    /// Any other error
+    Other(ErrorSource),
+}
+
+impl ServiceError {
+    /// Returns the original error
+    pub fn source_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        match self {
+            ServiceError::NotFound(source) => source.as_ref(),
+            ServiceError::AccessDenied(source) => source.as_ref(),
+            ServiceError::AlreadyExists(source) => source.as_ref(),
+            ServiceError::Timeout(source) => source.as_ref(),
+            ServiceError::InvalidArgument(source) => source.as_ref(),
+            ServiceError::Unavailable(source) => source.as_ref(),
+            ServiceError::Other(source) => source.as_ref(),
+        }
+    }
+
+    /// Returns [ErrorKind] of the matching `io::Error`
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ServiceError::NotFound(_) => ErrorKind::NotFound,
+            ServiceError::AccessDenied(_) => ErrorKind::PermissionDenied,
+            ServiceError::AlreadyExists(_) => ErrorKind::AlreadyExists,
+            ServiceError::Timeout(_) => ErrorKind::TimedOut,
+            ServiceError::InvalidArgument(_) => ErrorKind::InvalidInput,
+            ServiceError::Unavailable(_) => ErrorKind::ConnectionRefused,
+            ServiceError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    fn into_source(self) -> ErrorSource {
+        match self {
+            ServiceError::NotFound(source) => source,
+            ServiceError::AccessDenied(source) => source,
+            ServiceError::AlreadyExists(source) => source,
+            ServiceError::Timeout(source) => source,
+            ServiceError::InvalidArgument(source) => source,
+            ServiceError::Unavailable(source) => source,
+            ServiceError::Other(source) => source,
+        }
+    }
+}
+
+impl Display for ServiceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            ServiceError::NotFound(_) => "Service not found",
+            ServiceError::AccessDenied(_) => "Access denied",
+            ServiceError::AlreadyExists(_) => "Service already exists",
+            ServiceError::Timeout(_) => "Service manager timed out",
+            ServiceError::InvalidArgument(_) => "Invalid argument",
+            ServiceError::Unavailable(_) => "Service manager is not available",
+            ServiceError::Other(_) => "Service operation failed",
+        };
+
+        write!(f, "{}: {}", description, self.source_error())
+    }
+}
+
+impl StdError for ServiceError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.source_error())
+    }
+}
+
+impl From<Error> for ServiceError {
+    fn from(err: Error) -> Self {
+        #[cfg(windows)]
+        {
+            if let Some(code) = err.raw_os_error() {
+                match code {
+                    // ERROR_SERVICE_DOES_NOT_EXIST
+                    1060 => return ServiceError::NotFound(Box::new(err)),
+                    // ERROR_ACCESS_DENIED
+                    5 => return ServiceError::AccessDenied(Box::new(err)),
+                    // ERROR_SERVICE_EXISTS, ERROR_DUPLICATE_SERVICE_NAME
+                    1073 | 1078 => return ServiceError::AlreadyExists(Box::new(err)),
+                    // ERROR_SERVICE_REQUEST_TIMEOUT
+                    1053 => return ServiceError::Timeout(Box::new(err)),
+                    // ERROR_INVALID_PARAMETER, ERROR_INVALID_NAME, ERROR_INVALID_SERVICE_ACCOUNT
+                    87 | 123 | 1057 => return ServiceError::InvalidArgument(Box::new(err)),
+                    _ => {}
+                }
+            }
+        }
+
+        match err.kind() {
+            ErrorKind::NotFound => ServiceError::NotFound(Box::new(err)),
+            ErrorKind::PermissionDenied => ServiceError::AccessDenied(Box::new(err)),
+            ErrorKind::AlreadyExists => ServiceError::AlreadyExists(Box::new(err)),
+            ErrorKind::TimedOut => ServiceError::Timeout(Box::new(err)),
+            ErrorKind::InvalidInput | ErrorKind::InvalidData => ServiceError::InvalidArgument(Box::new(err)),
+            _ => ServiceError::Other(Box::new(err)),
+        }
+    }
+}
+
+/// Maps D-Bus error names of systemd and message bus to error variants
+#[cfg(unix)]
+impl From<dbus::Error> for ServiceError {
+    fn from(err: dbus::Error) -> Self {
+        match err.name().unwrap_or_default() {
+            "org.freedesktop.systemd1.NoSuchUnit"
+            | "org.freedesktop.systemd1.NoSuchJob"
+            | "org.freedesktop.systemd1.LoadFailed"
+            | "org.freedesktop.DBus.Error.FileNotFound"
+            | "org.freedesktop.DBus.Error.UnknownObject" => ServiceError::NotFound(Box::new(err)),
+            "org.freedesktop.DBus.Error.AccessDenied"
+            | "org.freedesktop.DBus.Error.AuthFailed"
+            | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" => ServiceError::AccessDenied(Box::new(err)),
+            "org.freedesktop.systemd1.UnitExists"
+            | "org.freedesktop.DBus.Error.FileExists" => ServiceError::AlreadyExists(Box::new(err)),
+            "org.freedesktop.DBus.Error.NoReply"
+            | "org.freedesktop.DBus.Error.Timeout"
+            | "org.freedesktop.DBus.Error.TimedOut" => ServiceError::Timeout(Box::new(err)),
+            "org.freedesktop.DBus.Error.InvalidArgs"
+            | "org.freedesktop.systemd1.BadUnitSetting"
+            | "org.freedesktop.systemd1.JobTypeNotApplicable"
+            | "org.freedesktop.systemd1.TransactionIsDestructive" => ServiceError::InvalidArgument(Box::new(err)),
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+            | "org.freedesktop.DBus.Error.NoServer"
+            | "org.freedesktop.DBus.Error.Disconnected"
+            | "org.freedesktop.DBus.Error.NoNetwork"
+            | "org.freedesktop.systemd1.ShuttingDown" => ServiceError::Unavailable(Box::new(err)),
+            _ => ServiceError::Other(Box::new(err)),
+        }
+    }
+}
+
+impl From<ServiceError> for Error {
+    fn from(err: ServiceError) -> Self {
+        let kind = err.kind();
+
+        match err.into_source().downcast::<Error>() {
+            Ok(err) => *err,
+            Err(source) => Error::new(kind, source),
+        }
+    }
+}
@@ -4,6 +4,8 @@ pub mod windows;
 #[cfg(unix)]
 pub mod unix;
 
+pub mod command_line;
+pub mod error;
 pub mod service;
 pub mod service_manager;
 
@@ -1,12 +1,31 @@
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io::Error;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
 #[cfg(unix)]
-use std::fs::OpenOptions;
+use std::net::{TcpListener, UdpSocket};
 #[cfg(unix)]
-use std::io::Write;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::os::unix::net::UnixListener;
 
 #[cfg(unix)]
-use signal_hook::{consts::SIGTERM, iterator::Signals};
+use signal_hook::{consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM}, iterator::Signals};
+#[cfg(all(unix, feature = "tokio"))]
+use tokio::signal::unix::{signal, SignalKind};
+
+#[cfg(unix)]
+use crate::unix::activation::{listen_fds, ListenFd, SocketKind};
+#[cfg(unix)]
+use crate::unix::journal;
+#[cfg(unix)]
+use crate::unix::notify::{notify, NotifyState, watchdog_interval};
+
+#[cfg(windows)]
+use std::sync::atomic::AtomicI32;
 
 #[cfg(windows)]
 use crate::windows::win32_api_services::{
@@ -24,25 +43,672 @@ use crate::windows::win32_api_services::{
 /// A function structure to call as service main function
 ///
 /// Argument indicate if service is running or stopped by operating system. The function should
-/// store the state and return when argument value became false
+/// store the state and return when argument value became false. Use [start_service_with] and
+/// [ShutdownToken] to block until service is stopped instead of polling the value.
 pub type ServiceMainFunc = fn(Arc<AtomicBool>);
 
+/// Exit code of the process when service main function returned an error
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code of the process when service did not stop before its stop timeout
+///
+/// See [ServiceContext::set_stop_timeout].
+pub const EXIT_STOP_TIMEOUT: i32 = 124;
+
+/// Error returned by service main function after conversion
+type MainError = Box<dyn StdError + Send + Sync>;
+
+/// Notifies threads that service is stopped by operating system
+///
+/// Token is cancelled when SIGTERM signal received on Linux or stop control sent by Windows
+/// service manager. Threads may block on [wait](ShutdownToken::wait) instead of polling a flag.
+/// Cloned tokens share the same state.
+#[derive(Clone)]
+pub struct ShutdownToken {
+    inner: Arc<ShutdownInner>,
+}
+
+struct ShutdownInner {
+    state: Mutex<ShutdownState>,
+    condvar: Condvar,
+}
+
+struct ShutdownState {
+    cancelled: bool,
+    callbacks: Vec<Box<dyn FnOnce() + Send>>,
+    wakers: Vec<Waker>,
+}
+
+impl ShutdownToken {
+    /// Creates a token which is not cancelled
+    pub fn new() -> Self {
+        ShutdownToken {
+            inner: Arc::new(ShutdownInner {
+                state: Mutex::new(ShutdownState {
+                    cancelled: false,
+                    callbacks: vec![],
+                    wakers: vec![],
+                }),
+                condvar: Condvar::new(),
+            })
+        }
+    }
+
+    /// Cancels the token, wakes up waiting threads and tasks and calls registered callbacks
+    ///
+    /// Callbacks are called in the current thread. Cancelling a cancelled token does nothing.
+    pub fn cancel(&self) {
+        let (callbacks, wakers) = {
+            let mut state = self.inner.state.lock().unwrap();
+            if state.cancelled {
+                return;
+            }
+
+            state.cancelled = true;
+            (std::mem::take(&mut state.callbacks), std::mem::take(&mut state.wakers))
+        };
+
+        self.inner.condvar.notify_all();
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        for callback in callbacks {
+            callback();
+        }
+    }
+
+    /// Indicate if the token is cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.state.lock().unwrap().cancelled
+    }
+
+    /// Blocks current thread until the token is cancelled
+    pub fn wait(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        while !state.cancelled {
+            state = self.inner.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// Blocks current thread until the token is cancelled or timeout elapsed
+    ///
+    /// Returns true if the token is cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let state = self.inner.state.lock().unwrap();
+        let (state, _) = self.inner.condvar
+            .wait_timeout_while(state, timeout, |state| !state.cancelled)
+            .unwrap();
+
+        state.cancelled
+    }
+
+    /// Returns a future which is completed when the token is cancelled
+    ///
+    /// The future does not depend on a specific async runtime.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+
+    /// Registers a callback to call when the token is cancelled
+    ///
+    /// The callback is called immediately if the token is already cancelled.
+    pub fn on_cancel<F>(&self, callback: F) where F: FnOnce() + Send + 'static {
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            if !state.cancelled {
+                state.callbacks.push(Box::new(callback));
+                return;
+            }
+        }
+
+        callback();
+    }
+
+    /// Returns a flag which is set to false when the token is cancelled
+    fn running_flag(&self) -> Arc<AtomicBool> {
+        let running = Arc::new(AtomicBool::new(!self.is_cancelled()));
+        let cb_running = running.clone();
+        self.on_cancel(move || cb_running.store(false, Ordering::Relaxed));
+
+        running
+    }
+}
+
+impl Default for ShutdownToken {
+    fn default() -> Self {
+        ShutdownToken::new()
+    }
+}
+
+/// Future returned by [ShutdownToken::cancelled]
+pub struct Cancelled {
+    token: ShutdownToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.token.inner.state.lock().unwrap();
+        if state.cancelled {
+            return Poll::Ready(());
+        }
+
+        if !state.wakers.iter().any(|it| it.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Provides events sent to service by operating system
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceEvent {
+    /// Service is stopped by SIGTERM signal on Linux or stop control on Windows
+    Stop,
+    /// Service should reload its configuration
+    ///
+    /// Sent by SIGHUP signal on Linux (`systemctl reload`) or *SERVICE_CONTROL_PARAMCHANGE*
+    /// control on Windows.
+    Reload,
+}
+
+/// Handlers registered by [ServiceContext::on_event]
+type EventHandlers = Arc<Mutex<Vec<Arc<dyn Fn(ServiceEvent) + Send + Sync>>>>;
+
+/// Calls registered handlers with specified event
+///
+/// Handlers are called on a snapshot taken under the lock, so a slow handler does not block
+/// registering new handlers, and a panicking handler does not poison the list.
+fn dispatch_event(handlers: &EventHandlers, event: ServiceEvent) {
+    let snapshot = handlers.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    for handler in snapshot.iter() {
+        handler(event);
+    }
+}
+
+/// Stop timeout configured by [ServiceContext::set_stop_timeout]
+struct StopState {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    hook: Option<Box<dyn FnOnce() + Send>>,
+    /// Indicate if service main function returned, which disarms the timer
+    finished: bool,
+}
+
+type StopTimer = Arc<Mutex<StopState>>;
+
+/// Starts a timer when service is stopped, which terminates the process after stop timeout
+fn start_stop_timer(shutdown: &ShutdownToken) -> StopTimer {
+    let stop: StopTimer = Arc::new(Mutex::new(StopState {
+        timeout: None,
+        deadline: None,
+        hook: None,
+        finished: false,
+    }));
+
+    let cb_stop = stop.clone();
+    shutdown.on_cancel(move || {
+        {
+            let mut state = cb_stop.lock().unwrap();
+            match state.timeout {
+                Some(timeout) if !state.finished => state.deadline = Some(Instant::now() + timeout),
+                _ => return,
+            }
+        }
+
+        std::thread::spawn(move || {
+            let hook = loop {
+                let remaining = {
+                    let mut state = cb_stop.lock().unwrap();
+                    if state.finished {
+                        return;
+                    }
+
+                    let remaining = state.deadline.unwrap().saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        break state.hook.take();
+                    }
+
+                    remaining
+                };
+
+                std::thread::sleep(remaining);
+            };
+
+            eprintln!("Service did not stop before stop timeout");
+
+            if let Some(hook) = hook {
+                hook();
+            }
+
+            std::process::exit(EXIT_STOP_TIMEOUT);
+        });
+    });
+
+    stop
+}
+
+/// Disarms the stop timer after service main function returned
+fn finish_stop_timer(stop: &StopTimer) {
+    let mut state = stop.lock().unwrap();
+    state.finished = true;
+    state.hook = None;
+}
+
+/// Provides service information to service main function started by [start_service_with]
+pub struct ServiceContext {
+    name: String,
+    shutdown: ShutdownToken,
+    running: Arc<AtomicBool>,
+    handlers: EventHandlers,
+    stop: StopTimer,
+    #[cfg(unix)]
+    listen_fds: Vec<ListenFd>,
+}
+
+impl ServiceContext {
+    /// Returns service name passed to [start_service_with]
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Returns a value which is set to false when service is stopped by operating system
+    pub fn running(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    /// Indicate if service is still running or stopped by operating system
+    pub fn is_running(&self) -> bool {
+        !self.shutdown.is_cancelled()
+    }
+
+    /// Returns a token which is cancelled when service is stopped by operating system
+    pub fn shutdown_token(&self) -> ShutdownToken {
+        self.shutdown.clone()
+    }
+
+    /// Registers a handler to call when an event is sent to service
+    ///
+    /// Handlers are called in the thread which receives signals or controls from operating
+    /// system. On Linux, when service already reported readiness by [notify_ready], handlers of
+    /// [ServiceEvent::Reload] are surrounded by *RELOADING=1* and *READY=1* notifications, so
+    /// `systemctl status` shows the service as reloading meanwhile. `systemctl reload` does not
+    /// wait for the handlers, since the installed `ExecReload=` only sends SIGHUP.
+    pub fn on_event<F>(&self, handler: F) where F: Fn(ServiceEvent) + Send + Sync + 'static {
+        self.handlers.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(handler));
+    }
+
+    /// Sets maximum time service main function may take to return after service is stopped
+    ///
+    /// When the timeout elapsed, specified hook is called as the last chance to flush state,
+    /// then the process exits with [EXIT_STOP_TIMEOUT]. The timer is disarmed when service main
+    /// function returns in time. Must be called before service is stopped. Use
+    /// [extend_stop_timeout](ServiceContext::extend_stop_timeout) while a cleanup is still making
+    /// progress.
+    pub fn set_stop_timeout<F>(&self, timeout: Duration, hook: F) where F: FnOnce() + Send + 'static {
+        let mut state = self.stop.lock().unwrap();
+        state.timeout = Some(timeout);
+        state.hook = Some(Box::new(hook));
+    }
+
+    /// Extends stop timeout, so it elapses no sooner than specified duration from now
+    ///
+    /// On Linux also sends *EXTEND_TIMEOUT_USEC* to systemd, so systemd does not kill the
+    /// service because of its own `TimeoutStopSec=` meanwhile. Does nothing while service is not
+    /// stopping.
+    pub fn extend_stop_timeout(&self, extension: Duration) {
+        {
+            let mut state = self.stop.lock().unwrap();
+            match state.deadline {
+                None => return,
+                Some(deadline) => {
+                    state.deadline = Some(deadline.max(Instant::now() + extension));
+                }
+            }
+        }
+
+        #[cfg(unix)]
+        let _ = notify(&[NotifyState::ExtendTimeout(extension)]);
+    }
+
+    /// Takes all sockets passed by systemd socket activation
+    ///
+    /// Sockets are in the order of `ListenStream=` and `ListenDatagram=` settings of socket units.
+    /// Taken sockets are no longer returned by other `take_*` functions.
+    #[cfg(unix)]
+    pub fn take_listen_fds(&mut self) -> Vec<ListenFd> {
+        std::mem::take(&mut self.listen_fds)
+    }
+
+    /// Takes the first socket passed by systemd with specified name
+    ///
+    /// Name is set by `FileDescriptorName=` setting of the socket unit, defaults to socket unit
+    /// name.
+    #[cfg(unix)]
+    pub fn take_listen_fd(&mut self, name: &str) -> Option<ListenFd> {
+        let index = self.listen_fds.iter().position(|it| it.name() == name)?;
+        Some(self.listen_fds.remove(index))
+    }
+
+    /// Takes the first listening TCP socket passed by systemd
+    #[cfg(unix)]
+    pub fn take_tcp_listener(&mut self) -> Option<TcpListener> {
+        self.take_listen_fd_of(SocketKind::TcpListener)?.into_tcp_listener().ok()
+    }
+
+    /// Takes the first UDP socket passed by systemd
+    #[cfg(unix)]
+    pub fn take_udp_socket(&mut self) -> Option<UdpSocket> {
+        self.take_listen_fd_of(SocketKind::UdpSocket)?.into_udp_socket().ok()
+    }
+
+    /// Takes the first listening Unix stream socket passed by systemd
+    #[cfg(unix)]
+    pub fn take_unix_listener(&mut self) -> Option<UnixListener> {
+        self.take_listen_fd_of(SocketKind::UnixListener)?.into_unix_listener().ok()
+    }
+
+    #[cfg(unix)]
+    fn take_listen_fd_of(&mut self, kind: SocketKind) -> Option<ListenFd> {
+        let index = self.listen_fds.iter().position(|it| it.kind() == kind)?;
+        Some(self.listen_fds.remove(index))
+    }
+}
+
+/// Indicate if service main function reported itself healthy, see [set_healthy]
+static HEALTHY: AtomicBool = AtomicBool::new(true);
+
+/// Indicate if service is running interactively from a console, see [is_running_as_service]
+static CONSOLE: AtomicBool = AtomicBool::new(false);
+
+/// Indicate if service reported its readiness to systemd, see [notify_ready]
+#[cfg(unix)]
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// Reports whether the service is healthy
+///
+/// On Linux, when the unit has `WatchdogSec=` setting, [start_service] sends keep-alive pings to
+/// systemd while the service is healthy. Reporting `false` stops the pings, so systemd treats the
+/// service as hung and restarts it according to its `Restart=` setting. Services are healthy by
+/// default. Has no effect on Windows.
+pub fn set_healthy(healthy: bool) {
+    HEALTHY.store(healthy, Ordering::Relaxed);
+}
+
+/// Indicate if current process is started by service manager
+///
+/// On Linux, the process is considered a service when systemd passed *INVOCATION_ID* or
+/// *NOTIFY_SOCKET* environment variables, or when standard input is not attached to a terminal.
+#[cfg(unix)]
+pub fn is_running_as_service() -> bool {
+    std::env::var_os("INVOCATION_ID").is_some()
+        || std::env::var_os("NOTIFY_SOCKET").is_some()
+        || unsafe { libc::isatty(libc::STDIN_FILENO) } != 1
+}
+
+/// Indicate if current process is started by service manager
+///
+/// On Windows, returns `false` after [start_service] failed to connect to service control manager
+/// and started the service in console mode.
+#[cfg(windows)]
+pub fn is_running_as_service() -> bool {
+    !CONSOLE.load(Ordering::Relaxed)
+}
+
+/// Writes service lifecycle transition to *stderr* when service is running in console mode
+fn log_transition(message: &str) {
+    if CONSOLE.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    }
+}
+
 /// Call specified function and wait for SIGTERM in another thread
 ///
+/// When systemd requests a watchdog (see *WATCHDOG_USEC* in
+/// [sd_watchdog_enabled](https://www.freedesktop.org/software/systemd/man/sd_watchdog_enabled.html)),
+/// keep-alive pings are sent in another thread until the service reports itself unhealthy by
+/// [set_healthy].
+///
 #[cfg(unix)]
 pub fn start_service(service_name: &str, service_main: ServiceMainFunc) {
-    let running = Arc::new(AtomicBool::new(true));
+    start_service_with(service_name, move |context| {
+        service_main(context.running());
+        Ok::<(), Error>(())
+    })
+}
+
+/// Call specified closure as service main function and wait for SIGTERM in another thread
+///
+/// SIGHUP signal is delivered to the service as [ServiceEvent::Reload]. Sockets passed by
+/// systemd socket activation are available from [ServiceContext].
+///
+/// Returning an error from the closure writes the error to *stderr*, reports *ERRNO* (for OS
+/// errors) and *EXIT_STATUS* to systemd, and exits the process with [EXIT_FAILURE], so systemd
+/// records a failure instead of a clean stop.
+///
+/// When the process is started from a terminal (see [is_running_as_service]), SIGINT (Ctrl+C)
+/// and SIGQUIT signals stop the service too, and lifecycle transitions are written to *stderr*.
+///
+#[cfg(unix)]
+pub fn start_service_with<F, E>(service_name: &str, service_main: F)
+    where F: FnOnce(ServiceContext) -> Result<(), E> + Send + 'static,
+          E: Into<Box<dyn StdError + Send + Sync>> {
+    let context = create_context(service_name);
 
-    let mut signals = Signals::new(&[SIGTERM]).unwrap();
-    let th_running = running.clone();
+    let mut signals = Signals::new(handled_signals()).unwrap();
+    let th_shutdown = context.shutdown.clone();
+    let th_handlers = context.handlers.clone();
 
     std::thread::spawn(move || {
-        for _ in signals.forever() {
-            th_running.store(false, Ordering::Relaxed);
+        for signal in signals.forever() {
+            handle_signal(signal, &th_shutdown, &th_handlers);
         }
     });
 
-    service_main(running.clone());
+    let stop = context.stop.clone();
+    let result = service_main(context);
+    finish_stop_timer(&stop);
+    log_transition("Service stopped");
+
+    if let Err(err) = result {
+        exit_with_error(err.into());
+    }
+}
+
+/// Call specified async closure as service main function on a tokio runtime
+///
+/// Behaves like [start_service_with], but signals are received by tokio signal handling instead of
+/// a dedicated thread. Use [ShutdownToken::cancelled] to await service stop.
+///
+#[cfg(all(unix, feature = "tokio"))]
+pub fn start_service_async<F, Fut, E>(service_name: &str, service_main: F)
+    where F: FnOnce(ServiceContext) -> Fut + Send + 'static,
+          Fut: Future<Output=Result<(), E>>,
+          E: Into<Box<dyn StdError + Send + Sync>> {
+    let context = create_context(service_name);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let result = runtime.block_on(async {
+        for number in handled_signals() {
+            let mut stream = signal(SignalKind::from_raw(number))?;
+            let th_shutdown = context.shutdown.clone();
+            let th_handlers = context.handlers.clone();
+
+            tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    handle_signal(number, &th_shutdown, &th_handlers);
+                }
+            });
+        }
+
+        let stop = context.stop.clone();
+        let result = service_main(context).await.map_err(Into::into);
+        finish_stop_timer(&stop);
+
+        result
+    });
+    log_transition("Service stopped");
+
+    if let Err(err) = result {
+        exit_with_error(err);
+    }
+}
+
+/// Creates context of the service and starts watchdog if requested by systemd
+#[cfg(unix)]
+fn create_context(service_name: &str) -> ServiceContext {
+    // Environment variables are removed before starting other threads
+    let listen_fds = listen_fds().unwrap_or_default();
+
+    CONSOLE.store(!is_running_as_service(), Ordering::Relaxed);
+    journal::set_identifier(service_name);
+    log_transition(format!("Starting service '{}' in console mode", service_name).as_str());
+
+    let shutdown = ShutdownToken::new();
+    let handlers: EventHandlers = Arc::new(Mutex::new(vec![]));
+    let stop = start_stop_timer(&shutdown);
+
+    let cb_handlers = handlers.clone();
+    shutdown.on_cancel(move || dispatch_event(&cb_handlers, ServiceEvent::Stop));
+
+    if let Some(interval) = watchdog_interval() {
+        start_watchdog(interval);
+    }
+
+    ServiceContext {
+        name: service_name.into(),
+        running: shutdown.running_flag(),
+        shutdown,
+        handlers,
+        stop,
+        listen_fds,
+    }
+}
+
+/// Returns signals handled by the runtime
+///
+/// SIGINT and SIGQUIT are handled only in console mode, so terminal keys stop the service.
+#[cfg(unix)]
+fn handled_signals() -> Vec<i32> {
+    let mut signals = vec![SIGTERM, SIGHUP];
+
+    if CONSOLE.load(Ordering::Relaxed) {
+        signals.push(SIGINT);
+        signals.push(SIGQUIT);
+    }
+
+    signals
+}
+
+/// Handles signals received by the runtime, SIGHUP reloads the service and others stop it
+#[cfg(unix)]
+fn handle_signal(signal: i32, shutdown: &ShutdownToken, handlers: &EventHandlers) {
+    if signal == SIGHUP {
+        log_transition("Reloading service");
+
+        // A service which is still starting must not be reported as ready by the reload
+        let ready = READY.load(Ordering::Relaxed);
+        if ready {
+            let _ = notify(&[NotifyState::Reloading]);
+        }
+
+        dispatch_event(handlers, ServiceEvent::Reload);
+
+        if ready {
+            let _ = notify(&[NotifyState::Ready]);
+        }
+        log_transition("Service reloaded");
+    } else {
+        log_transition(format!("Stopping service on signal {}", signal).as_str());
+        let _ = notify(&[NotifyState::Stopping]);
+        shutdown.cancel();
+    }
+}
+
+/// Reports service failure to systemd and exits the process
+#[cfg(unix)]
+fn exit_with_error(err: MainError) -> ! {
+    eprintln!("{}", err);
+
+    let mut states = vec![NotifyState::Status(err.to_string())];
+    if let Some(errno) = err.downcast_ref::<Error>().and_then(|it| it.raw_os_error()) {
+        states.push(NotifyState::Errno(errno));
+    }
+    states.push(NotifyState::ExitStatus(EXIT_FAILURE));
+
+    let _ = notify(&states);
+
+    std::process::exit(EXIT_FAILURE)
+}
+
+/// Sends keep-alive pings to systemd twice per watchdog interval while service is healthy
+#[cfg(unix)]
+fn start_watchdog(interval: Duration) {
+    std::thread::spawn(move || {
+        loop {
+            if HEALTHY.load(Ordering::Relaxed) {
+                let _ = notify(&[NotifyState::Watchdog]);
+            }
+
+            std::thread::sleep(interval / 2);
+        }
+    });
+}
+
+/// Notifies service manager that service startup is finished
+///
+/// On Linux sends *READY=1* and *MAINPID* to systemd, which is required when the unit is installed
+/// with `Type=notify` (see [ServiceInfo](crate::service_manager::ServiceInfo)). Call it after
+/// service has bound its sockets and is able to serve requests. Does nothing when process was not
+/// started by systemd.
+#[cfg(unix)]
+pub fn notify_ready() -> Result<(), Error> {
+    notify(&[NotifyState::Ready, NotifyState::MainPid(std::process::id())])?;
+    READY.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Sends a single-line status text to service manager
+///
+/// The status is shown by `systemctl status`. Does nothing when process was not started by systemd.
+#[cfg(unix)]
+pub fn notify_status(status: &str) -> Result<(), Error> {
+    notify(&[NotifyState::Status(status.into())])?;
+
+    Ok(())
+}
+
+/// Notifies service manager that service startup is finished
+///
+/// Windows service is reported as running before the service main function is called, so this
+/// function does nothing.
+#[cfg(windows)]
+pub fn notify_ready() -> Result<(), Error> {
+    Ok(())
+}
+
+/// Sends a single-line status text to service manager
+///
+/// Windows service manager does not keep status text, so this function does nothing.
+#[cfg(windows)]
+pub fn notify_status(_status: &str) -> Result<(), Error> {
+    Ok(())
 }
 
 /// Initialize current process as service then call specified function
@@ -51,21 +717,141 @@ pub fn start_service(service_name: &str, service_main: ServiceMainFunc) {
 ///
 #[cfg(windows)]
 pub fn start_service(service_name: &str, main_func: ServiceMainFunc) {
+    start_service_with(service_name, move |context| {
+        main_func(context.running());
+        Ok::<(), Error>(())
+    })
+}
+
+/// Initialize current process as service then call specified closure
+///
+/// Returning an error from the closure reports the service as stopped with an exit code
+/// (the OS error code, or [EXIT_FAILURE] as service specific exit code) and exits the process
+/// with [EXIT_FAILURE].
+///
+/// When the process is started from a console instead of service control manager, the closure is
+/// called directly and lifecycle transitions are written to *stderr* (see
+/// [is_running_as_service]). Console control events are not handled, so the service is stopped by
+/// terminating the process.
+///
+#[cfg(windows)]
+pub fn start_service_with<F, E>(service_name: &str, service_main: F)
+    where F: FnOnce(ServiceContext) -> Result<(), E> + Send + 'static,
+          E: Into<Box<dyn StdError + Send + Sync>> {
+    let shutdown = ShutdownToken::new();
+    let handlers: EventHandlers = Arc::new(Mutex::new(vec![]));
+    let stop = start_stop_timer(&shutdown);
+
+    let cb_handlers = handlers.clone();
+    shutdown.on_cancel(move || dispatch_event(&cb_handlers, ServiceEvent::Stop));
+
     unsafe {
-        RUNNING = Some(Arc::new(AtomicBool::new(true)));
-        MAIN_FUNC = Some(main_func);
+        SHUTDOWN = Some(shutdown);
+        HANDLERS = Some(handlers);
+        STOP = Some(stop);
+        MAIN_FUNC = Some(Box::new(move |context: ServiceContext| -> Result<(), MainError> {
+            service_main(context).map_err(Into::into)
+        }));
         SERVICE_NAME = Some(service_name.into());
     }
 
-    start_service_ctrl_dispatcher(service_name, Some(svc_main)).unwrap()
+    match start_service_ctrl_dispatcher(service_name, Some(svc_main)) {
+        Ok(_) => {}
+        Err(err) if err.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) => {
+            run_console();
+        }
+        Err(err) => panic!("{}", err),
+    }
+
+    let exit_code = EXIT_CODE.load(Ordering::Relaxed);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Initialize current process as service then call specified async closure on a tokio runtime
+///
+/// Behaves like [start_service_with]. Use [ShutdownToken::cancelled] to await service stop.
+///
+#[cfg(all(windows, feature = "tokio"))]
+pub fn start_service_async<F, Fut, E>(service_name: &str, service_main: F)
+    where F: FnOnce(ServiceContext) -> Fut + Send + 'static,
+          Fut: Future<Output=Result<(), E>>,
+          E: Into<Box<dyn StdError + Send + Sync>> {
+    start_service_with(service_name, move |context: ServiceContext| -> Result<(), MainError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+
+        runtime.block_on(service_main(context)).map_err(Into::into)
+    })
 }
 
+/// Provides ERROR_SERVICE_SPECIFIC_ERROR value
+#[cfg(windows)]
+const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+
+/// Provides ERROR_FAILED_SERVICE_CONTROLLER_CONNECT value, returned when process is not started
+/// by service control manager
+#[cfg(windows)]
+const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+
 #[cfg(windows)]
-static mut RUNNING: Option<Arc<AtomicBool>> = None;
+static mut SHUTDOWN: Option<ShutdownToken> = None;
 #[cfg(windows)]
-static mut MAIN_FUNC: Option<ServiceMainFunc> = None;
+static mut HANDLERS: Option<EventHandlers> = None;
+#[cfg(windows)]
+static mut STOP: Option<StopTimer> = None;
+#[cfg(windows)]
+static mut MAIN_FUNC: Option<Box<dyn FnOnce(ServiceContext) -> Result<(), MainError> + Send>> = None;
 #[cfg(windows)]
 static mut SERVICE_NAME: Option<String> = None;
+#[cfg(windows)]
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+/// Calls service main function stored by [start_service_with]
+#[cfg(windows)]
+fn run_main_func(service_name: String) -> Result<(), MainError> {
+    let (main_func, shutdown, handlers, stop) = unsafe {
+        (MAIN_FUNC.take(),
+         SHUTDOWN.as_ref().unwrap().clone(),
+         HANDLERS.as_ref().unwrap().clone(),
+         STOP.as_ref().unwrap().clone())
+    };
+
+    let result = match main_func {
+        None => Ok(()),
+        Some(main_func) => main_func(ServiceContext {
+            name: service_name,
+            running: shutdown.running_flag(),
+            shutdown,
+            handlers,
+            stop: stop.clone(),
+        }),
+    };
+    finish_stop_timer(&stop);
+
+    result
+}
+
+/// Runs service main function in current thread when process is started from a console
+#[cfg(windows)]
+fn run_console() {
+    let service_name = unsafe {
+        SERVICE_NAME.as_ref().unwrap().clone()
+    };
+
+    CONSOLE.store(true, Ordering::Relaxed);
+    log_transition(format!("Starting service '{}' in console mode", service_name).as_str());
+
+    let result = run_main_func(service_name);
+    log_transition("Service stopped");
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        EXIT_CODE.store(EXIT_FAILURE, Ordering::Relaxed);
+    }
+}
 
 #[cfg(windows)]
 fn svc_main(_: Vec<String>) {
@@ -79,25 +865,33 @@ fn svc_main(_: Vec<String>) {
     set_service_status(&handle, ServiceStatus {
         service_type: ServiceType::Win32OwnProcess,
         service_state: ServiceState::Running,
-        controls_accepted: vec![ServiceControlsAccepted::Stop],
+        controls_accepted: vec![ServiceControlsAccepted::Stop, ServiceControlsAccepted::ParamChange],
         win32_exit_code: 0,
         service_specific_exit_code: 0,
         check_point: 0,
         wait_hint: 0,
     }).expect("Cannot set service status");
 
-    unsafe {
-        if MAIN_FUNC.is_some() {
-            MAIN_FUNC.as_ref().unwrap()(RUNNING.as_ref().unwrap().clone());
+    let result = run_main_func(service_name);
+
+    let (win32_exit_code, service_specific_exit_code) = match result {
+        Ok(_) => (0, 0),
+        Err(err) => {
+            EXIT_CODE.store(EXIT_FAILURE, Ordering::Relaxed);
+
+            match err.downcast_ref::<Error>().and_then(|it| it.raw_os_error()) {
+                Some(code) => (code as u32, 0),
+                None => (ERROR_SERVICE_SPECIFIC_ERROR, EXIT_FAILURE as u32),
+            }
         }
-    }
+    };
 
     set_service_status(&handle, ServiceStatus {
         service_type: ServiceType::Win32OwnProcess,
         service_state: ServiceState::Stopped,
         controls_accepted: vec![],
-        win32_exit_code: 0,
-        service_specific_exit_code: 0,
+        win32_exit_code,
+        service_specific_exit_code,
         check_point: 0,
         wait_hint: 0,
     }).expect("Cannot set service status");
@@ -111,9 +905,175 @@ fn svc_ctrl(control: ServiceControl) {
     match control {
         ServiceControl::Stop => {
             unsafe {
-                RUNNING.as_ref().unwrap().store(false, Ordering::Relaxed);
+                SHUTDOWN.as_ref().unwrap().cancel();
+            }
+        }
+        ServiceControl::ParamChange => {
+            unsafe {
+                dispatch_event(HANDLERS.as_ref().unwrap(), ServiceEvent::Reload);
             }
         }
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    /// Wakes a thread blocked by [block_on]
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls specified future in current thread until it is completed
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn wait_timeout_returns_false_until_cancelled() {
+        let token = ShutdownToken::new();
+
+        assert!(!token.wait_timeout(Duration::from_millis(20)));
+        assert!(!token.is_cancelled());
+
+        token.cancel();
+
+        assert!(token.wait_timeout(Duration::from_millis(20)));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn wait_returns_when_cancelled_from_another_thread() {
+        let token = ShutdownToken::new();
+        let th_token = token.clone();
+
+        let handle = std::thread::spawn(move || th_token.wait_timeout(Duration::from_secs(10)));
+        std::thread::sleep(Duration::from_millis(20));
+        token.cancel();
+
+        assert!(handle.join().unwrap());
+        token.wait();
+    }
+
+    #[test]
+    fn on_cancel_runs_immediately_when_cancelled() {
+        let token = ShutdownToken::new();
+        token.cancel();
+
+        let called = Arc::new(AtomicBool::new(false));
+        let cb_called = called.clone();
+        token.on_cancel(move || cb_called.store(true, Ordering::SeqCst));
+
+        assert!(called.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn callbacks_run_once() {
+        let token = ShutdownToken::new();
+        let count = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            let cb_count = count.clone();
+            token.on_cancel(move || {
+                cb_count.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        token.clone().cancel();
+        token.cancel();
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn running_flag_is_cleared_when_cancelled() {
+        let token = ShutdownToken::new();
+        let running = token.running_flag();
+
+        assert!(running.load(Ordering::Relaxed));
+        token.cancel();
+        assert!(!running.load(Ordering::Relaxed));
+        assert!(!token.running_flag().load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn cancelled_resolves_when_cancelled_from_another_thread() {
+        let token = ShutdownToken::new();
+        let th_token = token.clone();
+
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            th_token.cancel();
+        });
+
+        block_on(token.cancelled());
+        assert!(token.is_cancelled());
+        handle.join().unwrap();
+
+        block_on(token.cancelled());
+    }
+
+    fn context(shutdown: &ShutdownToken) -> ServiceContext {
+        ServiceContext {
+            name: "test-service".into(),
+            running: shutdown.running_flag(),
+            shutdown: shutdown.clone(),
+            handlers: Arc::new(Mutex::new(vec![])),
+            stop: start_stop_timer(shutdown),
+            #[cfg(unix)]
+            listen_fds: vec![],
+        }
+    }
+
+    #[test]
+    fn stop_timer_is_disarmed_when_service_returns() {
+        let shutdown = ShutdownToken::new();
+        let context = context(&shutdown);
+        let stop = context.stop.clone();
+
+        let called = Arc::new(AtomicBool::new(false));
+        let cb_called = called.clone();
+        context.set_stop_timeout(Duration::from_millis(50), move || cb_called.store(true, Ordering::SeqCst));
+
+        shutdown.cancel();
+        drop(context);
+        finish_stop_timer(&stop);
+
+        // The process exits with EXIT_STOP_TIMEOUT if the timer is still armed
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!called.load(Ordering::SeqCst));
+        assert!(stop.lock().unwrap().hook.is_none());
+    }
+
+    #[test]
+    fn stop_timer_is_not_started_after_service_returned() {
+        let shutdown = ShutdownToken::new();
+        let context = context(&shutdown);
+        let stop = context.stop.clone();
+
+        context.set_stop_timeout(Duration::from_millis(10), || {});
+        drop(context);
+        finish_stop_timer(&stop);
+        shutdown.cancel();
+
+        assert!(stop.lock().unwrap().deadline.is_none());
+    }
+}
@@ -1,24 +1,46 @@
-use std::io::Error;
-
 #[cfg(unix)]
+use std::ffi::CString;
+use std::ffi::OsString;
+#[cfg(unix)]
+use std::fs::remove_file;
+use std::io::{Error, ErrorKind};
+#[cfg(unix)]
+use std::mem::MaybeUninit;
+use std::path::PathBuf;
 use std::time::Duration;
+#[cfg(windows)]
+use std::time::Instant;
+
+#[cfg(windows)]
+use crate::command_line::quote_command_line;
+use crate::error::ServiceError;
 
 #[cfg(unix)]
 use crate::unix::services::{
-    delete_unit,
-    disable_unit_files,
-    enable_unit_files,
-    install_string,
-    Mode,
+    reload_or_restart_unit,
+    reload_unit,
+    restart_unit,
+    JobResult,
+    ServiceManager,
+    ServiceProperties,
     start_unit,
     stop_unit,
+    try_restart_unit,
+    unit_file_path,
+    write_private_file,
 };
+#[cfg(unix)]
+use crate::unix::unit_file::{self, UnitFile};
+#[cfg(all(unix, feature = "tokio"))]
+use crate::unix::services_async;
 
 #[cfg(windows)]
 use crate::windows::{
     win_service_manager::{
         control_windows_service,
         delete_windows_service,
+        get_service_status,
+        get_service_status_process,
         install_windows_service,
         start_windows_service,
         WindowsServiceInfo,
@@ -26,23 +48,121 @@ use crate::windows::{
     win32_api_services::{
         SERVICE_ALL_ACCESS,
         ServiceControl,
+        ServiceControlsAccepted,
         ServiceErrorControl,
         ServiceStartType,
+        ServiceState as WindowsServiceState,
         ServiceType},
 };
 
+/// Provides job modes of systemd, which decide how a job conflicting with queued jobs is handled
+///
+/// For more information visit [systemctl](https://www.freedesktop.org/software/systemd/man/systemctl.html#--job-mode=).
+/// Ignored on Windows.
+pub enum Mode {
+    /// Replaces conflicting queued jobs
+    Replace,
+    /// Fails when a conflicting job is queued
+    Fail,
+    /// Stops all other units, only valid for starting a unit
+    Isolate,
+    /// Ignores all unit dependencies
+    IgnoreDependencies,
+    /// Ignores requirement dependencies, but honors ordering
+    IgnoreRequirements,
+}
+
+impl From<Mode> for &'static str {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Replace => "replace",
+            Mode::Fail => "fail",
+            Mode::Isolate => "isolate",
+            Mode::IgnoreDependencies => "ignore-dependencies",
+            Mode::IgnoreRequirements => "ignore-requirements",
+        }
+    }
+}
+
 #[cfg(unix)]
 fn get_unit_name(service_name: &str) -> String {
     format!("{}.service", service_name)
 }
 
+#[cfg(unix)]
+fn get_socket_unit_name(service_name: &str) -> String {
+    format!("{}.socket", service_name)
+}
+
+#[cfg(unix)]
+fn get_environment_file_name(service_name: &str) -> String {
+    format!("{}.env", service_name)
+}
+
+/// Returns an error if specified user or group does not exist on this host
+///
+/// Numeric names are looked up as user or group ids, as systemd accepts them too.
+#[cfg(unix)]
+fn check_account(name: &str, is_group: bool) -> Result<(), ServiceError> {
+    let kind = if is_group { "Group" } else { "User" };
+    let invalid = || ServiceError::InvalidArgument(Error::new(
+        ErrorKind::InvalidInput,
+        format!("{} '{}' does not exist", kind, name)).into());
+
+    let c_name = CString::new(name).map_err(|_| invalid())?;
+    let id: Option<u32> = name.parse().ok();
+
+    let exists = match (is_group, id) {
+        (false, Some(uid)) => account_exists::<libc::passwd>(|entry, buffer, length, result| unsafe {
+            libc::getpwuid_r(uid, entry, buffer, length, result)
+        }),
+        (false, None) => account_exists::<libc::passwd>(|entry, buffer, length, result| unsafe {
+            libc::getpwnam_r(c_name.as_ptr(), entry, buffer, length, result)
+        }),
+        (true, Some(gid)) => account_exists::<libc::group>(|entry, buffer, length, result| unsafe {
+            libc::getgrgid_r(gid, entry, buffer, length, result)
+        }),
+        (true, None) => account_exists::<libc::group>(|entry, buffer, length, result| unsafe {
+            libc::getgrnam_r(c_name.as_ptr(), entry, buffer, length, result)
+        }),
+    }?;
+
+    if exists { Ok(()) } else { Err(invalid()) }
+}
+
+/// Calls specified reentrant lookup function of user or group database, e.g. *getpwnam_r*
+///
+/// The buffer is grown while the function reports `ERANGE`. Lookup failures other than a missing
+/// entry, e.g. an unavailable NSS backend, are returned as errors.
+#[cfg(unix)]
+fn account_exists<T>(mut lookup: impl FnMut(*mut T, *mut libc::c_char, usize, *mut *mut T) -> libc::c_int)
+                     -> Result<bool, ServiceError> {
+    let mut buffer: Vec<libc::c_char> = vec![0; 1024];
+
+    loop {
+        let mut entry = MaybeUninit::<T>::uninit();
+        let mut result = std::ptr::null_mut();
+
+        match lookup(entry.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) {
+            0 => return Ok(!result.is_null()),
+            libc::ERANGE => {
+                let length = buffer.len() * 2;
+                buffer.resize(length, 0);
+            }
+            // Some implementations report a missing entry by these errors, instead of null result
+            libc::ENOENT | libc::ESRCH => return Ok(false),
+            code => return Err(ServiceError::Other(Error::from_raw_os_error(code).into())),
+        }
+    }
+}
+
 /// Starts specified service
 ///
 /// Uses D-Bus StartUnit function from path '/org/freedesktop/systemd1' to start the specified service.
 ///
 /// Appends ".service" at the end of service name.
 #[cfg(unix)]
-pub fn start(service_name: &str) -> Result<(), Error> {
+pub fn start(service_name: &str) -> Result<(), ServiceError> {
     start_unit(get_unit_name(service_name).as_str(),
                Mode::Replace,
                Duration::from_secs(30))
@@ -54,20 +174,208 @@ pub fn start(service_name: &str) -> Result<(), Error> {
 ///
 /// Appends ".service" at the end of service name.
 #[cfg(unix)]
-pub fn stop(service_name: &str) -> Result<(), Error> {
+pub fn stop(service_name: &str) -> Result<(), ServiceError> {
     stop_unit(get_unit_name(service_name).as_str(),
               Mode::Replace,
               Duration::from_secs(30))
 }
 
+/// Restarts specified service, starts it if it is not running
+///
+/// Uses D-Bus RestartUnit function from path '/org/freedesktop/systemd1'.
+///
+/// Appends ".service" at the end of service name.
+#[cfg(unix)]
+pub fn restart(service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+    restart_unit(get_unit_name(service_name).as_str(), mode, Duration::from_secs(30))
+}
+
+/// Restarts specified service if it is running
+///
+/// Uses D-Bus TryRestartUnit function from path '/org/freedesktop/systemd1'.
+///
+/// Appends ".service" at the end of service name.
+#[cfg(unix)]
+pub fn try_restart(service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+    try_restart_unit(get_unit_name(service_name).as_str(), mode, Duration::from_secs(30))
+}
+
+/// Reloads configuration of specified service
+///
+/// Uses D-Bus ReloadUnit function from path '/org/freedesktop/systemd1'. The service must be
+/// installed with [reloadable](ServiceInfo::reloadable).
+///
+/// Appends ".service" at the end of service name.
+#[cfg(unix)]
+pub fn reload(service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+    reload_unit(get_unit_name(service_name).as_str(), mode, Duration::from_secs(30))
+}
+
+/// Reloads configuration of specified service if it supports reloading, otherwise restarts it
+///
+/// Uses D-Bus ReloadOrRestartUnit function from path '/org/freedesktop/systemd1'.
+///
+/// Appends ".service" at the end of service name.
+#[cfg(unix)]
+pub fn reload_or_restart(service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+    reload_or_restart_unit(get_unit_name(service_name).as_str(), mode, Duration::from_secs(30))
+}
+
+/// Starts specified service without blocking current thread
+///
+/// Async version of [start] using non-blocking D-Bus connection. Must be called within a tokio
+/// runtime.
+#[cfg(all(unix, feature = "tokio"))]
+pub async fn start_async(service_name: &str) -> Result<(), ServiceError> {
+    services_async::start_unit(get_unit_name(service_name).as_str(),
+                               Mode::Replace,
+                               Duration::from_secs(30)).await
+}
+
+/// Stops specified service without blocking current thread
+///
+/// Async version of [stop] using non-blocking D-Bus connection. Must be called within a tokio
+/// runtime.
+#[cfg(all(unix, feature = "tokio"))]
+pub async fn stop_async(service_name: &str) -> Result<(), ServiceError> {
+    services_async::stop_unit(get_unit_name(service_name).as_str(),
+                              Mode::Replace,
+                              Duration::from_secs(30)).await
+}
+
 /// Deletes specified service
 ///
-/// Deletes service unit file from path "/etc/systemd/system".
+/// Stops the service and waits until it is stopped, disables it, deletes its unit file and
+/// drop-in directory from path "/etc/systemd/system", reloads systemd configuration and resets
+/// its failed state. Socket unit is deleted the same way if service was installed with
+/// [sockets](ServiceInfo::sockets), and the environment file written for
+/// [private_environment](ServiceInfo::private_environment) is deleted.
+///
+/// A failed or skipped step does not stop the next steps, see [DeleteReport].
 ///
 /// Appends ".service" at the end of service name.
 #[cfg(unix)]
-pub fn delete(service_name: &str) -> Result<(), Error> {
-    delete_unit(get_unit_name(service_name).as_str())
+pub fn delete(service_name: &str) -> Result<DeleteReport, ServiceError> {
+    let name = get_unit_name(service_name);
+    let socket_name = get_socket_unit_name(service_name);
+    // Socket unit is stopped first, so it does not activate the service again
+    let units = [socket_name.as_str(), name.as_str()];
+
+    let mut manager = ServiceManager::new(Duration::from_secs(30))?;
+    manager.set_auto_reload(false);
+
+    let mut report = DeleteReport { steps: vec![] };
+
+    for unit in units.iter() {
+        let result = match manager.stop_unit_and_wait(unit, Mode::Replace) {
+            Ok(JobResult::Done) | Ok(JobResult::Skipped) => Ok(()),
+            Ok(result) => Err(ServiceError::Other(format!("Stop job result is {:?}", result).into())),
+            Err(err) => Err(err),
+        };
+
+        report.push(format!("Stop {}", unit), result);
+    }
+
+    for unit in units.iter() {
+        report.push(format!("Disable {}", unit), manager.disable_unit_files(vec![unit]).map(|_| ()));
+    }
+
+    for unit in units.iter() {
+        report.push(format!("Delete {}", unit), manager.delete_unit(unit));
+        report.push(format!("Delete {}.d", unit), manager.delete_drop_in_dir(unit));
+    }
+
+    let environment_file_name = get_environment_file_name(service_name);
+    report.push(format!("Delete {}", environment_file_name),
+                remove_file(unit_file_path(environment_file_name.as_str())).map_err(ServiceError::from));
+
+    report.push("Reload systemd configuration".into(), manager.reload_daemon());
+
+    for unit in units.iter() {
+        report.push(format!("Reset failed state of {}", unit), manager.reset_failed_unit(unit));
+    }
+
+    Ok(report)
+}
+
+/// Provides outcome of a step of service deletion
+#[derive(Debug)]
+pub enum StepResult {
+    /// Step is done successfully
+    Done,
+    /// Step is skipped, because the service or the file it removes does not exist
+    Skipped,
+    /// Step failed by specified error
+    Failed(ServiceError),
+}
+
+/// Provides a step of service deletion
+#[derive(Debug)]
+pub struct DeleteStep {
+    /// Description of the step, e.g. "Stop my-service.service"
+    pub name: String,
+    /// Outcome of the step
+    pub result: StepResult,
+}
+
+/// Provides steps taken by [delete]
+#[derive(Debug)]
+pub struct DeleteReport {
+    /// Steps in the order they were taken
+    pub steps: Vec<DeleteStep>,
+}
+
+impl DeleteReport {
+    /// Indicate if no step failed
+    pub fn is_success(&self) -> bool {
+        self.steps.iter().all(|it| !matches!(it.result, StepResult::Failed(_)))
+    }
+
+    /// Converts the report to an error of the first failed step
+    pub fn into_result(self) -> Result<(), ServiceError> {
+        for step in self.steps {
+            if let StepResult::Failed(err) = step.result {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a step, a [NotFound](ServiceError::NotFound) error is recorded as skipped step
+    fn push(&mut self, name: String, result: Result<(), ServiceError>) {
+        let result = match result {
+            Ok(_) => StepResult::Done,
+            Err(ServiceError::NotFound(_)) => StepResult::Skipped,
+            Err(err) => StepResult::Failed(err),
+        };
+
+        self.steps.push(DeleteStep { name, result });
+    }
+}
+
+/// Provides a change of unit file symlinks made by enabling or disabling units
+///
+/// Returned by [install] on Linux. Windows services have no unit files, so no changes are
+/// reported there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitFileChange {
+    /// Type of the change, "symlink" or "unlink"
+    pub change_type: String,
+    /// Path of the created or removed symlink
+    pub file_name: String,
+    /// Destination of the created symlink, empty for removed symlinks
+    pub destination: String,
+}
+
+impl From<(String, String, String)> for UnitFileChange {
+    fn from(value: (String, String, String)) -> Self {
+        UnitFileChange {
+            change_type: value.0,
+            file_name: value.1,
+            destination: value.2,
+        }
+    }
 }
 
 /// Contains required information for service installation
@@ -77,32 +385,421 @@ pub struct ServiceInfo {
     /// Service description
     pub description: String,
     /// Executable file path of the service
+    ///
+    /// Contains only the path, arguments are passed by [args](ServiceInfo::args). The path is
+    /// quoted when it contains whitespace.
     pub exec_path: String,
+    /// Command line arguments passed to the service executable
+    ///
+    /// On Linux arguments are quoted for `ExecStart=` word splitting, and `%` and `$` are escaped
+    /// so systemd does not expand them. On Windows they are quoted following *CommandLineToArgvW*
+    /// rules in the binary path of the service.
+    pub args: Vec<String>,
+    /// Environment variables set for the service
+    ///
+    /// On Linux written as `Environment=` lines of the unit, or to an environment file when
+    /// [private_environment](ServiceInfo::private_environment) is set. Ignored on Windows.
+    pub environment: Vec<(String, String)>,
+    /// Files to read environment variables from, written as `EnvironmentFile=`
+    ///
+    /// Paths must be absolute. Ignored on Windows.
+    pub environment_files: Vec<PathBuf>,
+    /// Indicate if [environment](ServiceInfo::environment) contains secrets
+    ///
+    /// On Linux the variables are written to "/etc/systemd/system/<name>.env" file with mode 0600
+    /// instead of the unit file, which is readable by every user. Ignored on Windows.
+    pub private_environment: bool,
+    /// User account the service runs as, the service runs as root or *LocalSystem* if not set
+    ///
+    /// On Linux written as `User=`, and the user must exist on this host. On Windows used as
+    /// service account name, e.g. `.\my-user` or `NT AUTHORITY\NetworkService`.
+    pub user: Option<String>,
+    /// Password of [user](ServiceInfo::user) account on Windows
+    ///
+    /// Not needed for built-in accounts. Ignored on Linux.
+    pub password: Option<String>,
+    /// Group the service runs as, written as `Group=`
+    ///
+    /// The group must exist on this host. Ignored on Windows.
+    pub group: Option<String>,
+    /// Additional groups of the service process, written as `SupplementaryGroups=`
+    ///
+    /// The groups must exist on this host. Ignored on Windows.
+    pub supplementary_groups: Vec<String>,
+    /// Working directory of the service, written as `WorkingDirectory=`
+    ///
+    /// Path must be absolute. Ignored on Windows.
+    pub working_directory: Option<PathBuf>,
     /// Indicate if service should start at booting time
     pub auto_start: bool,
+    /// Indicate if service reports its readiness by calling
+    /// [notify_ready](crate::service::notify_ready)
+    ///
+    /// On Linux the unit is written with `Type=notify`, so systemd considers the service started
+    /// only after the notification is received. Ignored on Windows.
+    pub notify: bool,
+    /// Watchdog interval of the service
+    ///
+    /// On Linux the unit is written with `WatchdogSec=`, and
+    /// [start_service](crate::service::start_service) sends keep-alive pings to systemd while the
+    /// service is healthy. Ignored on Windows.
+    pub watchdog: Option<Duration>,
+    /// Indicate if service reloads its configuration on
+    /// [ServiceEvent::Reload](crate::service::ServiceEvent::Reload)
+    ///
+    /// On Linux the unit is written with `ExecReload=/bin/kill -HUP $MAINPID`, so
+    /// `systemctl reload` sends SIGHUP signal to the service. Ignored on Windows.
+    pub reloadable: bool,
+    /// Sockets to listen on behalf of the service
+    ///
+    /// On Linux a socket unit with the same name is installed next to the service unit, and
+    /// listening sockets are passed to the service by socket activation (see
+    /// [ServiceContext](crate::service::ServiceContext)), so restarting the service does not
+    /// drop connections. Ignored on Windows.
+    pub sockets: Vec<Listen>,
+    /// Targets which want the service when it is enabled by [auto_start](ServiceInfo::auto_start),
+    /// defaults to `multi-user.target`
+    ///
+    /// On Linux written as `WantedBy=` in `[Install]` section. Ignored on Windows.
+    pub wanted_by: Vec<String>,
+    /// Targets which require the service when it is enabled, written as `RequiredBy=`
+    ///
+    /// Ignored on Windows.
+    pub required_by: Vec<String>,
+    /// Additional names of the service created as symlinks when it is enabled, written as
+    /// `Alias=`
+    ///
+    /// Ignored on Windows.
+    pub alias: Vec<String>,
+    /// Additional units enabled or disabled together with the service, written as `Also=`
+    ///
+    /// Ignored on Windows.
+    pub also: Vec<String>,
+}
+
+impl Default for ServiceInfo {
+    fn default() -> Self {
+        ServiceInfo {
+            name: String::new(),
+            description: String::new(),
+            exec_path: String::new(),
+            args: vec![],
+            environment: vec![],
+            environment_files: vec![],
+            private_environment: false,
+            user: None,
+            password: None,
+            group: None,
+            supplementary_groups: vec![],
+            working_directory: None,
+            auto_start: false,
+            notify: false,
+            watchdog: None,
+            reloadable: false,
+            sockets: vec![],
+            wanted_by: vec!["multi-user.target".into()],
+            required_by: vec![],
+            alias: vec![],
+            also: vec![],
+        }
+    }
+}
+
+impl ServiceInfo {
+    /// Sets [args](ServiceInfo::args) from OS strings, e.g. arguments of current process
+    ///
+    /// Returns an error if an argument is not valid Unicode, since it can not be written to a unit
+    /// file or a Windows binary path.
+    pub fn set_args_os<I, S>(&mut self, args: I) -> Result<(), ServiceError>
+        where I: IntoIterator<Item=S>, S: Into<OsString> {
+        self.args = args.into_iter()
+            .map(|it| it.into().into_string().map_err(|it| {
+                ServiceError::InvalidArgument(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Argument {:?} is not valid Unicode", it)).into())
+            }))
+            .collect::<Result<Vec<String>, ServiceError>>()?;
+
+        Ok(())
+    }
+}
+
+/// Provides a socket address systemd listens on behalf of the service
+///
+/// Address may be a port number, an IP address with port or a Unix socket path. For more
+/// information visit [systemd.socket](https://www.freedesktop.org/software/systemd/man/systemd.socket.html)
+pub enum Listen {
+    /// Stream socket, written as `ListenStream=`
+    Stream(String),
+    /// Datagram socket, written as `ListenDatagram=`
+    Datagram(String),
+}
+
+/// Provides platform independent state of a service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServiceState {
+    /// Service is not installed
+    NotInstalled,
+    /// Service is installed and not running
+    Stopped,
+    /// Service is starting, or waiting to be restarted automatically
+    Starting,
+    /// Service is running
+    Running,
+    /// Service is reloading its configuration
+    Reloading,
+    /// Service is stopping
+    Stopping,
+    /// Service is paused, or pausing (Windows only)
+    Paused,
+    /// Service stopped by a failure
+    Failed,
+    /// Service manager reported a state unknown to this crate
+    Unknown,
+}
+
+/// Provides status of a service, see [status]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStatus {
+    /// Current state of the service
+    pub state: ServiceState,
+    /// Process ID of the service main process, if it is running
+    pub main_pid: Option<u32>,
+    /// Exit code of the last service run, if it exited with an exit code
+    pub exit_code: Option<i32>,
+}
+
+impl ServiceStatus {
+    fn not_installed() -> ServiceStatus {
+        ServiceStatus {
+            state: ServiceState::NotInstalled,
+            main_pid: None,
+            exit_code: None,
+        }
+    }
+}
+
+/// Maps systemd *ActiveState* and *SubState* of a unit to [ServiceState]
+#[cfg(unix)]
+pub(crate) fn to_service_state(active_state: &str, sub_state: &str) -> ServiceState {
+    match (active_state, sub_state) {
+        ("active", "reload") | ("reloading", _) => ServiceState::Reloading,
+        ("active", _) => ServiceState::Running,
+        ("activating", _) => ServiceState::Starting,
+        ("deactivating", _) => ServiceState::Stopping,
+        ("inactive", _) | ("maintenance", _) => ServiceState::Stopped,
+        ("failed", _) => ServiceState::Failed,
+        _ => ServiceState::Unknown,
+    }
+}
+
+/// Gets status of specified service
+///
+/// Maps systemd *ActiveState* and *SubState* of the service unit to [ServiceState]. Exit code is
+/// reported only when the main process exited by itself, not when it was killed by a signal.
+///
+/// Appends ".service" at the end of service name.
+#[cfg(unix)]
+pub fn status(service_name: &str) -> Result<ServiceStatus, ServiceError> {
+    let manager = ServiceManager::new(Duration::from_secs(30))?;
+    let properties = manager.get_service_properties(get_unit_name(service_name).as_str())?;
+
+    Ok(to_service_status(properties))
+}
+
+/// Gets status of specified service without blocking current thread
+///
+/// Async version of [status] using non-blocking D-Bus connection. Must be called within a tokio
+/// runtime.
+#[cfg(all(unix, feature = "tokio"))]
+pub async fn status_async(service_name: &str) -> Result<ServiceStatus, ServiceError> {
+    let properties = services_async::get_service_properties(get_unit_name(service_name).as_str(),
+                                                            Duration::from_secs(30)).await?;
+
+    Ok(to_service_status(properties))
+}
+
+#[cfg(unix)]
+fn to_service_status(properties: Option<ServiceProperties>) -> ServiceStatus {
+    let properties = match properties {
+        None => return ServiceStatus::not_installed(),
+        Some(value) => value,
+    };
+
+    ServiceStatus {
+        state: to_service_state(properties.active_state.as_str(), properties.sub_state.as_str()),
+        main_pid: Some(properties.main_pid).filter(|it| *it != 0),
+        exit_code: Some(properties.exec_main_status)
+            .filter(|_| properties.exec_main_code == libc::CLD_EXITED),
+    }
 }
 
 /// Installs service by specified service information
 ///
-/// Adds new service file to path "/etc/systemd/system".
+/// Adds new service file to path "/etc/systemd/system", and reloads systemd configuration before
+/// enabling or disabling the service. Returns symlinks created or removed by enabling or
+/// disabling the units.
 ///
 /// Appends ".service" at the end of service name.
 #[cfg(unix)]
-pub fn install(service_info: ServiceInfo) -> Result<(), Error> {
-    let service_file = format!("[Unit]
-Description={}
+pub fn install(service_info: ServiceInfo) -> Result<Vec<UnitFileChange>, ServiceError> {
+    let name = get_unit_name(service_info.name.as_str());
+    let socket_name = get_socket_unit_name(service_info.name.as_str());
+    let service_type = if service_info.notify { "notify" } else { "simple" };
+
+    let mut service_file = UnitFile::new();
+
+    let unit = service_file.section_mut("Unit");
+    unit.add("Description", service_info.description.as_str());
+
+    if !service_info.sockets.is_empty() {
+        unit.add("Requires", socket_name.as_str())
+            .add("After", socket_name.as_str());
+    }
+
+    let command: Vec<&str> = std::iter::once(service_info.exec_path.as_str())
+        .chain(service_info.args.iter().map(|it| it.as_str()))
+        .collect();
+
+    let service = service_file.section_mut("Service");
+    service.add("Type", service_type)
+        .add_command("ExecStart", command.as_slice());
+
+    if service_info.reloadable {
+        service.add_raw("ExecReload", "/bin/kill -HUP $MAINPID");
+    }
+
+    if let Some(watchdog) = service_info.watchdog {
+        service.add("WatchdogSec", format!("{}ms", watchdog.as_millis()).as_str());
+    }
+
+    if let Some(user) = service_info.user.as_ref() {
+        check_account(user, false)?;
+        service.add("User", user);
+    }
+
+    if let Some(group) = service_info.group.as_ref() {
+        check_account(group, true)?;
+        service.add("Group", group);
+    }
+
+    if !service_info.supplementary_groups.is_empty() {
+        for group in service_info.supplementary_groups.iter() {
+            check_account(group, true)?;
+        }
+
+        service.add("SupplementaryGroups", service_info.supplementary_groups.join(" ").as_str());
+    }
+
+    if let Some(path) = service_info.working_directory.as_ref() {
+        match path.to_str() {
+            Some(path_str) if path.is_absolute() => service.add("WorkingDirectory", path_str),
+            _ => return Err(ServiceError::InvalidArgument(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid working directory {:?}", path)).into())),
+        };
+    }
+
+    let environment_file_name = get_environment_file_name(service_info.name.as_str());
+    let mut environment = None;
+
+    if service_info.private_environment && !service_info.environment.is_empty() {
+        environment = Some(unit_file::environment_file(service_info.environment.as_slice())?);
+
+        let path = unit_file_path(environment_file_name.as_str());
+        service.add("EnvironmentFile", path.to_string_lossy().as_ref());
+    } else {
+        for (key, value) in service_info.environment.iter() {
+            service.add_environment(key, value)?;
+        }
+    }
+
+    for path in service_info.environment_files.iter() {
+        match path.to_str() {
+            Some(path_str) if path.is_absolute() => service.add("EnvironmentFile", path_str),
+            _ => return Err(ServiceError::InvalidArgument(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid environment file path {:?}", path)).into())),
+        };
+    }
+
+    let install = [
+        ("WantedBy", &service_info.wanted_by),
+        ("RequiredBy", &service_info.required_by),
+        ("Alias", &service_info.alias),
+        ("Also", &service_info.also),
+    ];
+
+    for (key, values) in install.iter().filter(|(_, values)| !values.is_empty()) {
+        service_file.section_mut("Install").add(key, values.join(" ").as_str());
+    }
+
+    let service_file = service_file.serialize()?;
 
-[Service]
-Type=simple
-ExecStart={}", service_info.description, service_info.exec_path);
+    let mut units = vec![name.as_str()];
+    let mut socket_file = None;
 
-    let name = format!("{}.service", service_info.name.as_str());
-    install_string(name.as_str(), service_file.as_str())?;
+    if !service_info.sockets.is_empty() {
+        let mut file = UnitFile::new();
+        file.section_mut("Unit").add("Description", service_info.description.as_str());
+
+        let socket = file.section_mut("Socket");
+        for it in service_info.sockets.iter() {
+            match it {
+                Listen::Stream(address) => socket.add("ListenStream", address.as_str()),
+                Listen::Datagram(address) => socket.add("ListenDatagram", address.as_str()),
+            };
+        }
+
+        file.section_mut("Install").add("WantedBy", "sockets.target");
+        socket_file = Some(file.serialize()?);
+        units.push(socket_name.as_str());
+    }
+
+    // Environment file of an existing service is not replaced
+    if unit_file_path(name.as_str()).exists() {
+        return Err(ServiceError::AlreadyExists(
+            Error::new(ErrorKind::AlreadyExists, "File path already exists").into()));
+    }
+
+    let mut manager = ServiceManager::new(Duration::from_secs(30))?;
+    manager.set_auto_reload(false);
+
+    // Files are written in order they are referenced, and removed if a later one fails
+    let mut written = vec![];
+    let result = (|| -> Result<(), ServiceError> {
+        if let Some(environment) = environment.as_ref() {
+            write_private_file(environment_file_name.as_str(), environment.as_str())?;
+            written.push(environment_file_name.as_str());
+        }
+
+        manager.install_string(name.as_str(), service_file.as_str())?;
+        written.push(name.as_str());
+
+        if let Some(socket_file) = socket_file.as_ref() {
+            manager.install_string(socket_name.as_str(), socket_file.as_str())?;
+            written.push(socket_name.as_str());
+        }
+
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        for file_name in written {
+            let _ = remove_file(unit_file_path(file_name));
+        }
+
+        return Err(err);
+    }
+
+    // Unit files are loaded once, before enabling them
+    manager.reload_daemon()?;
 
     if service_info.auto_start {
-        enable_unit_files(vec![name.as_str()], Duration::from_secs(30))
+        manager.enable_unit_files(units)
     } else {
-        disable_unit_files(vec![name.as_str()], Duration::from_secs(30))
+        manager.disable_unit_files(units)
     }
 }
 
@@ -112,8 +809,8 @@ ExecStart={}", service_info.description, service_info.exec_path);
 /// Windows API function to start service
 ///
 #[cfg(windows)]
-pub fn start(service_name: &str) -> Result<(), Error> {
-    start_windows_service(service_name)
+pub fn start(service_name: &str) -> Result<(), ServiceError> {
+    start_windows_service(service_name).map_err(ServiceError::from)
 }
 
 /// Stops service in operating system
@@ -122,31 +819,219 @@ pub fn start(service_name: &str) -> Result<(), Error> {
 /// Windows API to send *SERVICE_CONTROL_STOP* to specified service.
 ///
 #[cfg(windows)]
-pub fn stop(service_name: &str) -> Result<(), Error> {
-    control_windows_service(service_name, ServiceControl::Stop)
+pub fn stop(service_name: &str) -> Result<(), ServiceError> {
+    control_windows_service(service_name, ServiceControl::Stop).map_err(ServiceError::from)
+}
+
+/// Provides ERROR_SERVICE_NOT_ACTIVE value
+#[cfg(windows)]
+const ERROR_SERVICE_NOT_ACTIVE: i32 = 1062;
+
+/// Restarts specified service, starts it if it is not running
+///
+/// Windows has no restart command, so the service is stopped, and started again after service
+/// control manager reports it as stopped.
+///
+#[cfg(windows)]
+pub fn restart(service_name: &str, _mode: Mode) -> Result<(), ServiceError> {
+    match control_windows_service(service_name, ServiceControl::Stop) {
+        Err(err) if err.raw_os_error() != Some(ERROR_SERVICE_NOT_ACTIVE) => return Err(err.into()),
+        _ => {}
+    }
+
+    wait_for_stopped(service_name, Duration::from_secs(30))?;
+
+    start(service_name)
+}
+
+/// Restarts specified service if it is running
+///
+/// See [restart].
+///
+#[cfg(windows)]
+pub fn try_restart(service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+    let status = get_service_status(service_name)?;
+
+    if status.service_state == WindowsServiceState::Running {
+        restart(service_name, mode)
+    } else {
+        Ok(())
+    }
+}
+
+/// Reloads configuration of specified service
+///
+/// Uses [ControlService](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-controlservice)
+/// Windows API to send *SERVICE_CONTROL_PARAMCHANGE* to specified service, which is delivered as
+/// [ServiceEvent::Reload](crate::service::ServiceEvent::Reload).
+///
+#[cfg(windows)]
+pub fn reload(service_name: &str, _mode: Mode) -> Result<(), ServiceError> {
+    control_windows_service(service_name, ServiceControl::ParamChange).map_err(ServiceError::from)
+}
+
+/// Reloads configuration of specified service if it supports reloading, otherwise restarts it
+///
+/// Reloading is supported when the running service accepts *SERVICE_CONTROL_PARAMCHANGE*.
+///
+#[cfg(windows)]
+pub fn reload_or_restart(service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+    let status = get_service_status(service_name)?;
+
+    if status.controls_accepted.contains(&ServiceControlsAccepted::ParamChange) {
+        reload(service_name, mode)
+    } else {
+        restart(service_name, mode)
+    }
+}
+
+/// Waits until specified service is reported as stopped by service control manager
+#[cfg(windows)]
+fn wait_for_stopped(service_name: &str, timeout: Duration) -> Result<(), ServiceError> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let status = get_service_status(service_name)?;
+
+        if status.service_state == WindowsServiceState::Stopped {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(ServiceError::Timeout(
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "Service did not stop in time").into()));
+        }
+
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+/// Starts specified service without blocking current thread
+///
+/// Async version of [start], which calls Windows API in a blocking task. Must be called within a
+/// tokio runtime.
+#[cfg(all(windows, feature = "tokio"))]
+pub async fn start_async(service_name: &str) -> Result<(), ServiceError> {
+    let service_name = service_name.to_string();
+
+    tokio::task::spawn_blocking(move || start(service_name.as_str())).await
+        .map_err(|err| ServiceError::Other(err.into()))?
+}
+
+/// Stops specified service without blocking current thread
+///
+/// Async version of [stop], which calls Windows API in a blocking task. Must be called within a
+/// tokio runtime.
+#[cfg(all(windows, feature = "tokio"))]
+pub async fn stop_async(service_name: &str) -> Result<(), ServiceError> {
+    let service_name = service_name.to_string();
+
+    tokio::task::spawn_blocking(move || stop(service_name.as_str())).await
+        .map_err(|err| ServiceError::Other(err.into()))?
+}
+
+/// Gets status of specified service without blocking current thread
+///
+/// Async version of [status], which calls Windows API in a blocking task. Must be called within a
+/// tokio runtime.
+#[cfg(all(windows, feature = "tokio"))]
+pub async fn status_async(service_name: &str) -> Result<ServiceStatus, ServiceError> {
+    let service_name = service_name.to_string();
+
+    tokio::task::spawn_blocking(move || status(service_name.as_str())).await
+        .map_err(|err| ServiceError::Other(err.into()))?
 }
 
 /// Deletes specified service
 ///
-/// Uses [DeleteService](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-deleteservice)
-/// Windows API to delete specified service name.
+/// Stops the service and waits until it is stopped, then uses
+/// [DeleteService](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-deleteservice)
+/// Windows API to delete specified service name. A failed or skipped step does not stop the next
+/// steps, see [DeleteReport].
 ///
 #[cfg(windows)]
-pub fn delete(service_name: &str) -> Result<(), Error> {
-    delete_windows_service(service_name)
+pub fn delete(service_name: &str) -> Result<DeleteReport, ServiceError> {
+    let mut report = DeleteReport { steps: vec![] };
+
+    match control_windows_service(service_name, ServiceControl::Stop) {
+        Err(err) if err.raw_os_error() == Some(ERROR_SERVICE_NOT_ACTIVE) => {
+            report.steps.push(DeleteStep {
+                name: format!("Stop {}", service_name),
+                result: StepResult::Skipped,
+            });
+        }
+        result => {
+            let result = result.map_err(ServiceError::from)
+                .and_then(|_| wait_for_stopped(service_name, Duration::from_secs(30)));
+
+            report.push(format!("Stop {}", service_name), result);
+        }
+    }
+
+    report.push(format!("Delete {}", service_name),
+                delete_windows_service(service_name).map_err(ServiceError::from));
+
+    Ok(report)
+}
+
+/// Provides ERROR_SERVICE_SPECIFIC_ERROR value
+#[cfg(windows)]
+const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+
+/// Provides ERROR_SERVICE_NEVER_STARTED value
+#[cfg(windows)]
+const ERROR_SERVICE_NEVER_STARTED: u32 = 1077;
+
+/// Gets status of specified service
+///
+/// Uses [QueryServiceStatusEx](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-queryservicestatusex)
+/// Windows API to get service state and process ID. A stopped service with a non-zero exit code
+/// is reported as [ServiceState::Failed].
+///
+#[cfg(windows)]
+pub fn status(service_name: &str) -> Result<ServiceStatus, ServiceError> {
+    let status = match get_service_status_process(service_name).map_err(ServiceError::from) {
+        Ok(value) => value,
+        Err(ServiceError::NotFound(_)) => return Ok(ServiceStatus::not_installed()),
+        Err(err) => return Err(err),
+    };
+
+    let exit_code = match status.status.win32_exit_code {
+        0 | ERROR_SERVICE_NEVER_STARTED => None,
+        ERROR_SERVICE_SPECIFIC_ERROR => Some(status.status.service_specific_exit_code as i32),
+        code => Some(code as i32),
+    };
+
+    let state = match status.status.service_state {
+        WindowsServiceState::Stopped if exit_code.is_some() => ServiceState::Failed,
+        WindowsServiceState::Stopped => ServiceState::Stopped,
+        WindowsServiceState::StartPending | WindowsServiceState::ContinuePending => ServiceState::Starting,
+        WindowsServiceState::Running => ServiceState::Running,
+        WindowsServiceState::StopPending => ServiceState::Stopping,
+        WindowsServiceState::PausePending | WindowsServiceState::Paused => ServiceState::Paused,
+    };
+
+    Ok(ServiceStatus {
+        state,
+        main_pid: Some(status.process_id).filter(|it| *it != 0),
+        exit_code,
+    })
 }
 
 /// Installs service by specified service information
 ///
 /// Uses [CreateServiceW](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-createservicew)
-/// Windows API to create service.
+/// Windows API to create service. Returns no [UnitFileChange], since Windows services have no
+/// unit files.
 ///
 #[cfg(windows)]
-pub fn install(service_info: ServiceInfo) -> Result<(), Error> {
+pub fn install(service_info: ServiceInfo) -> Result<Vec<UnitFileChange>, ServiceError> {
     let start = if service_info.auto_start {
         ServiceStartType::AutoStart
     } else { ServiceStartType::DemandStart };
 
+    let args: Vec<&str> = service_info.args.iter().map(|it| it.as_str()).collect();
+
     let w_service_info = WindowsServiceInfo {
         name: service_info.name.clone(),
         display_name: service_info.name,
@@ -155,13 +1040,48 @@ pub fn install(service_info: ServiceInfo) -> Result<(), Error> {
         service_type: ServiceType::Win32OwnProcess,
         start_type: start,
         error_control: ServiceErrorControl::Normal,
-        binary_path: service_info.exec_path,
+        binary_path: quote_command_line(service_info.exec_path.as_str(), args.as_slice()),
         load_order_group: None,
         tag_id: None,
         dependencies: None,
-        service_start_name: None,
-        password: None,
+        service_start_name: service_info.user,
+        password: service_info.password,
     };
 
-    install_windows_service(w_service_info)
+    install_windows_service(w_service_info)?;
+
+    Ok(vec![])
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn is_invalid_argument(result: Result<(), ServiceError>) -> bool {
+        matches!(result, Err(ServiceError::InvalidArgument(_)))
+    }
+
+    #[test]
+    fn finds_existing_user_and_group() {
+        check_account("root", false).unwrap();
+        check_account("0", false).unwrap();
+        check_account("0", true).unwrap();
+    }
+
+    #[test]
+    fn rejects_missing_user_and_group() {
+        assert!(is_invalid_argument(check_account("cross-platform-service-missing-user", false)));
+        assert!(is_invalid_argument(check_account("cross-platform-service-missing-group", true)));
+    }
+
+    #[test]
+    fn rejects_missing_numeric_ids() {
+        assert!(is_invalid_argument(check_account("3999999999", false)));
+        assert!(is_invalid_argument(check_account("3999999999", true)));
+    }
+
+    #[test]
+    fn rejects_names_with_null_character() {
+        assert!(is_invalid_argument(check_account("root\0", false)));
+    }
 }
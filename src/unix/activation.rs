@@ -0,0 +1,190 @@
+use std::env;
+use std::io::{Error, ErrorKind};
+use std::mem::{size_of, MaybeUninit};
+use std::net::{TcpListener, UdpSocket};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixListener;
+
+/// First file descriptor passed by systemd, see SD_LISTEN_FDS_START
+const LISTEN_FDS_START: RawFd = 3;
+/// Name of environment variable systemd uses to pass number of sockets
+const LISTEN_FDS: &str = "LISTEN_FDS";
+/// Name of environment variable systemd uses to pass process ID the sockets are passed to
+const LISTEN_PID: &str = "LISTEN_PID";
+/// Name of environment variable systemd uses to pass colon-separated names of sockets
+const LISTEN_FDNAMES: &str = "LISTEN_FDNAMES";
+
+/// Provides type of a socket passed by systemd
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SocketKind {
+    /// Listening TCP socket (`ListenStream=` with an IP address or port)
+    TcpListener,
+    /// UDP socket (`ListenDatagram=` with an IP address or port)
+    UdpSocket,
+    /// Listening Unix stream socket (`ListenStream=` with a path)
+    UnixListener,
+    /// Any other file descriptor, e.g. FIFO or Unix datagram socket
+    Other,
+}
+
+/// Provides a socket passed to service by systemd socket activation
+///
+/// The file descriptor is owned by this value, and is closed when it is dropped without being
+/// converted to a socket.
+///
+/// For more information visit [sd_listen_fds](https://www.freedesktop.org/software/systemd/man/sd_listen_fds.html)
+pub struct ListenFd {
+    fd: OwnedFd,
+    name: String,
+}
+
+impl ListenFd {
+    /// Returns file descriptor of the socket
+    pub fn raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Returns name of the socket set by `FileDescriptorName=`, defaults to socket unit name
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Detects type of the socket
+    pub fn kind(&self) -> SocketKind {
+        let fd = self.raw_fd();
+        let socket_type = match get_socket_option(fd, libc::SO_TYPE) {
+            Ok(value) => value,
+            Err(_) => return SocketKind::Other,
+        };
+        let listening = get_socket_option(fd, libc::SO_ACCEPTCONN)
+            .map(|value| value != 0)
+            .unwrap_or(false);
+
+        match (get_socket_family(fd), socket_type, listening) {
+            (Some(libc::AF_INET), libc::SOCK_STREAM, true) |
+            (Some(libc::AF_INET6), libc::SOCK_STREAM, true) => SocketKind::TcpListener,
+            (Some(libc::AF_INET), libc::SOCK_DGRAM, _) |
+            (Some(libc::AF_INET6), libc::SOCK_DGRAM, _) => SocketKind::UdpSocket,
+            (Some(libc::AF_UNIX), libc::SOCK_STREAM, true) => SocketKind::UnixListener,
+            _ => SocketKind::Other,
+        }
+    }
+
+    /// Converts the socket to [TcpListener]
+    ///
+    /// Fails when the socket is not a listening TCP socket.
+    pub fn into_tcp_listener(self) -> Result<TcpListener, Error> {
+        self.check_kind(SocketKind::TcpListener)?;
+        Ok(TcpListener::from(self.fd))
+    }
+
+    /// Converts the socket to [UdpSocket]
+    ///
+    /// Fails when the socket is not a UDP socket.
+    pub fn into_udp_socket(self) -> Result<UdpSocket, Error> {
+        self.check_kind(SocketKind::UdpSocket)?;
+        Ok(UdpSocket::from(self.fd))
+    }
+
+    /// Converts the socket to [UnixListener]
+    ///
+    /// Fails when the socket is not a listening Unix stream socket.
+    pub fn into_unix_listener(self) -> Result<UnixListener, Error> {
+        self.check_kind(SocketKind::UnixListener)?;
+        Ok(UnixListener::from(self.fd))
+    }
+
+    /// Converts the socket to [OwnedFd], e.g. for sockets of [SocketKind::Other] kind
+    pub fn into_owned_fd(self) -> OwnedFd {
+        self.fd
+    }
+
+    fn check_kind(&self, kind: SocketKind) -> Result<(), Error> {
+        if self.kind() == kind {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::InvalidInput,
+                           format!("Socket '{}' is not {:?}", self.name, kind)))
+        }
+    }
+}
+
+/// Returns sockets passed to current process by systemd
+///
+/// Reads *LISTEN_FDS*, *LISTEN_PID* and *LISTEN_FDNAMES* environment variables and removes them,
+/// so they are not inherited by child processes. Returns an empty list when process was not
+/// activated by a socket unit. Passed file descriptors are marked as close-on-exec.
+pub fn listen_fds() -> Result<Vec<ListenFd>, Error> {
+    let pid = env::var(LISTEN_PID).ok();
+    let count = env::var(LISTEN_FDS).ok();
+    let names = env::var(LISTEN_FDNAMES).ok();
+
+    env::remove_var(LISTEN_PID);
+    env::remove_var(LISTEN_FDS);
+    env::remove_var(LISTEN_FDNAMES);
+
+    let (pid, count) = match (pid, count) {
+        (Some(pid), Some(count)) => (pid, count),
+        _ => return Ok(vec![]),
+    };
+
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Ok(vec![]);
+    }
+
+    let count = count.parse::<RawFd>()
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid LISTEN_FDS value"))?;
+
+    let names: Vec<String> = match names {
+        Some(value) => value.split(':').map(|it| it.to_string()).collect(),
+        None => vec![],
+    };
+
+    let mut result = vec![];
+    for index in 0..count {
+        let fd = LISTEN_FDS_START + index;
+
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        result.push(ListenFd {
+            // Passed descriptors are owned by this process since LISTEN_PID matches
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            name: names.get(index as usize).cloned().unwrap_or_else(|| "unknown".into()),
+        });
+    }
+
+    Ok(result)
+}
+
+fn get_socket_option(fd: RawFd, option: libc::c_int) -> Result<libc::c_int, Error> {
+    let mut value: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(fd, libc::SOL_SOCKET, option,
+                         &mut value as *mut _ as *mut libc::c_void, &mut len)
+    };
+
+    if result < 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(value)
+    }
+}
+
+fn get_socket_family(fd: RawFd) -> Option<libc::c_int> {
+    let mut address = MaybeUninit::<libc::sockaddr_storage>::zeroed();
+    let mut len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockname(fd, address.as_mut_ptr() as *mut libc::sockaddr, &mut len)
+    };
+
+    if result < 0 {
+        None
+    } else {
+        Some(unsafe { address.assume_init() }.ss_family as libc::c_int)
+    }
+}
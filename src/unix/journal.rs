@@ -0,0 +1,413 @@
+use std::io::{Error, ErrorKind, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use log::kv::{Key, Value, VisitSource};
+
+/// Path of the socket journald receives native protocol datagrams on
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Fields set by the logger itself, which are not overridden by custom fields
+const RESERVED_FIELDS: [&str; 3] = ["MESSAGE", "PRIORITY", "SYSLOG_IDENTIFIER"];
+
+/// Service name passed to [start_service](crate::service::start_service)
+static IDENTIFIER: Mutex<Option<String>> = Mutex::new(None);
+
+/// Stores service name, which is used as *SYSLOG_IDENTIFIER* of log entries
+pub(crate) fn set_identifier(name: &str) {
+    *IDENTIFIER.lock().unwrap() = Some(name.into());
+}
+
+/// Logger sends log entries to systemd journal by
+/// [native protocol](https://systemd.io/JOURNAL_NATIVE_PROTOCOL/)
+///
+/// Each entry contains *MESSAGE*, *PRIORITY* (mapped from log level), *SYSLOG_IDENTIFIER*, code
+/// location, [custom fields](JournalLogger::fields) and key-values of the log record. Field names
+/// are converted to upper case, and fields with invalid names are dropped. Custom fields named
+/// *MESSAGE*, *PRIORITY* or *SYSLOG_IDENTIFIER* are dropped too, since they are set by the logger.
+///
+/// When journal socket does not exist (e.g. process is not started by systemd), or an entry is too
+/// large for a single datagram, entries are written to *stderr* with `<N>` priority prefix, which
+/// is understood by systemd for services started with stderr connected to journal.
+///
+/// ```no_run
+/// use cross_platform_service::unix::journal::JournalLogger;
+///
+/// JournalLogger {
+///     fields: vec![("COMPONENT".into(), "worker".into())],
+///     ..Default::default()
+/// }.init().unwrap();
+///
+/// log::info!("Service started");
+/// ```
+pub struct JournalLogger {
+    /// Value of *SYSLOG_IDENTIFIER* field
+    ///
+    /// When not set, service name passed to [start_service](crate::service::start_service) is
+    /// used, or executable name if the service is not started yet.
+    pub identifier: Option<String>,
+    /// Custom fields attached to every log entry
+    pub fields: Vec<(String, String)>,
+    /// Maximum level of log entries to send
+    pub level: LevelFilter,
+    /// Path of journald socket, defaults to [JOURNAL_SOCKET]
+    ///
+    /// May refer to another datagram socket to receive log entries instead of journald.
+    pub socket_path: PathBuf,
+}
+
+impl Default for JournalLogger {
+    fn default() -> Self {
+        JournalLogger {
+            identifier: None,
+            fields: vec![],
+            level: LevelFilter::Info,
+            socket_path: PathBuf::from(JOURNAL_SOCKET),
+        }
+    }
+}
+
+impl JournalLogger {
+    /// Sets the logger as global logger of [log] crate
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    /// Sends a log entry with specified priority, message and fields to journal
+    ///
+    /// Priority is a syslog level from 0 (emergency) to 7 (debug). Falls back to *stderr* when
+    /// journal socket does not exist or the entry is too large.
+    pub fn send(&self, priority: u8, message: &str, fields: &[(&str, &str)]) -> Result<(), Error> {
+        let mut entry = JournalEntry::new(priority, message, self.identifier().as_str());
+
+        for (name, value) in self.fields.iter() {
+            entry.append_custom(name, value.as_bytes());
+        }
+
+        for (name, value) in fields {
+            entry.append_custom(name, value.as_bytes());
+        }
+
+        self.write(&entry)
+    }
+
+    fn identifier(&self) -> String {
+        if let Some(identifier) = self.identifier.as_ref() {
+            return identifier.clone();
+        }
+
+        if let Some(identifier) = IDENTIFIER.lock().unwrap().as_ref() {
+            return identifier.clone();
+        }
+
+        std::env::args_os().next()
+            .as_ref()
+            .and_then(|it| Path::new(it).file_name())
+            .map(|it| it.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, entry: &JournalEntry) -> Result<(), Error> {
+        self.write_to(entry, &mut std::io::stderr())
+    }
+
+    /// Sends specified entry to journal socket, or writes its message to specified fallback
+    fn write_to(&self, entry: &JournalEntry, fallback: &mut dyn Write) -> Result<(), Error> {
+        let socket = UnixDatagram::unbound()?;
+
+        match socket.send_to(entry.buffer.as_slice(), self.socket_path.as_path()) {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound
+                || err.kind() == ErrorKind::ConnectionRefused
+                || err.raw_os_error() == Some(libc::EMSGSIZE) => {
+                for line in entry.message.lines() {
+                    writeln!(fallback, "<{}>{}", entry.priority, line)?;
+                }
+
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Log for JournalLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        let mut entry = JournalEntry::new(
+            priority(record.level()), message.as_str(), self.identifier().as_str());
+
+        if let Some(file) = record.file() {
+            entry.append("CODE_FILE", file.as_bytes());
+        }
+        if let Some(line) = record.line() {
+            entry.append("CODE_LINE", line.to_string().as_bytes());
+        }
+        if let Some(module) = record.module_path() {
+            entry.append("CODE_MODULE", module.as_bytes());
+        }
+        entry.append("TARGET", record.target().as_bytes());
+
+        for (name, value) in self.fields.iter() {
+            entry.append_custom(name, value.as_bytes());
+        }
+
+        let _ = record.key_values().visit(&mut entry);
+
+        if let Err(err) = self.write(&entry) {
+            eprintln!("Cannot write to journal: {}", err);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Maps log level to syslog priority
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug => 7,
+        Level::Trace => 7,
+    }
+}
+
+/// Serialized fields of a journal entry
+struct JournalEntry {
+    buffer: Vec<u8>,
+    priority: u8,
+    message: String,
+}
+
+impl JournalEntry {
+    fn new(priority: u8, message: &str, identifier: &str) -> JournalEntry {
+        let mut entry = JournalEntry {
+            buffer: vec![],
+            priority,
+            message: message.into(),
+        };
+
+        entry.append("PRIORITY", priority.to_string().as_bytes());
+        entry.append("MESSAGE", message.as_bytes());
+        entry.append("SYSLOG_IDENTIFIER", identifier.as_bytes());
+
+        entry
+    }
+
+    /// Appends a field to the entry, values containing new line are written with their length
+    fn append(&mut self, name: &str, value: &[u8]) {
+        let name = match field_name(name) {
+            None => return,
+            Some(name) => name,
+        };
+
+        self.buffer.extend_from_slice(name.as_bytes());
+
+        if value.contains(&b'\n') {
+            self.buffer.push(b'\n');
+            self.buffer.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            self.buffer.push(b'=');
+        }
+
+        self.buffer.extend_from_slice(value);
+        self.buffer.push(b'\n');
+    }
+
+    /// Appends a field which is not set by the logger, fields named as reserved fields are dropped
+    fn append_custom(&mut self, name: &str, value: &[u8]) {
+        if !RESERVED_FIELDS.contains(&name.to_ascii_uppercase().as_str()) {
+            self.append(name, value);
+        }
+    }
+}
+
+impl<'kvs> VisitSource<'kvs> for JournalEntry {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.append_custom(key.as_str(), value.to_string().as_bytes());
+
+        Ok(())
+    }
+}
+
+/// Converts specified name to a valid journal field name
+///
+/// Field names consist of upper case letters, digits and underscores, must not start with a digit
+/// or an underscore (which is reserved for trusted fields) and are at most 64 characters.
+fn field_name(name: &str) -> Option<String> {
+    let name = name.to_ascii_uppercase();
+
+    let valid = !name.is_empty() && name.len() <= 64
+        && !name.starts_with(|it: char| it == '_' || it.is_ascii_digit())
+        && name.chars().all(|it| it == '_' || it.is_ascii_uppercase() || it.is_ascii_digit());
+
+    if valid { Some(name) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// Binds a datagram socket in temporary directory, standing in for journald
+    fn bind_socket() -> (UnixDatagram, PathBuf) {
+        let mut path = std::env::temp_dir();
+        path.push(format!("journal-test-{}-{}.sock",
+                          std::process::id(), SOCKET_COUNTER.fetch_add(1, Ordering::SeqCst)));
+        let _ = std::fs::remove_file(&path);
+
+        (UnixDatagram::bind(&path).unwrap(), path)
+    }
+
+    fn logger(socket_path: PathBuf) -> JournalLogger {
+        JournalLogger {
+            identifier: Some("test-service".into()),
+            fields: vec![("component".into(), "worker".into())],
+            level: LevelFilter::Trace,
+            socket_path,
+        }
+    }
+
+    fn receive(socket: &UnixDatagram) -> Vec<u8> {
+        let mut buffer = vec![0; 65536];
+        let size = socket.recv(buffer.as_mut_slice()).unwrap();
+        buffer.truncate(size);
+
+        buffer
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|it| it == needle)
+    }
+
+    fn count(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack.windows(needle.len()).filter(|it| *it == needle).count()
+    }
+
+    #[test]
+    fn maps_levels_to_priorities() {
+        assert_eq!(priority(Level::Error), 3);
+        assert_eq!(priority(Level::Warn), 4);
+        assert_eq!(priority(Level::Info), 6);
+        assert_eq!(priority(Level::Debug), 7);
+        assert_eq!(priority(Level::Trace), 7);
+    }
+
+    #[test]
+    fn sends_record_with_priority_and_fields() {
+        let (socket, path) = bind_socket();
+        let logger = logger(path.clone());
+
+        logger.log(&Record::builder()
+            .args(format_args!("Disk is almost full"))
+            .level(Level::Warn)
+            .target("storage")
+            .build());
+
+        let datagram = receive(&socket);
+        let _ = std::fs::remove_file(path);
+
+        assert!(contains(&datagram, b"PRIORITY=4\n"));
+        assert!(contains(&datagram, b"MESSAGE=Disk is almost full\n"));
+        assert!(contains(&datagram, b"SYSLOG_IDENTIFIER=test-service\n"));
+        assert!(contains(&datagram, b"TARGET=storage\n"));
+        assert!(contains(&datagram, b"COMPONENT=worker\n"));
+    }
+
+    #[test]
+    fn sends_custom_fields_in_upper_case() {
+        let (socket, path) = bind_socket();
+        let logger = logger(path.clone());
+
+        logger.send(2, "Started", &[("request_id", "42"), ("invalid-name", "x")]).unwrap();
+
+        let datagram = receive(&socket);
+        let _ = std::fs::remove_file(path);
+
+        assert!(contains(&datagram, b"PRIORITY=2\n"));
+        assert!(contains(&datagram, b"REQUEST_ID=42\n"));
+        assert!(!contains(&datagram, b"invalid-name"));
+        assert!(!contains(&datagram, b"INVALID-NAME"));
+    }
+
+    #[test]
+    fn drops_custom_fields_with_reserved_names() {
+        let (socket, path) = bind_socket();
+        let mut logger = logger(path.clone());
+        logger.fields.push(("message".into(), "overridden".into()));
+
+        logger.send(6, "Original", &[("PRIORITY", "0"), ("syslog_identifier", "other")]).unwrap();
+
+        let datagram = receive(&socket);
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(count(&datagram, b"MESSAGE"), 1);
+        assert_eq!(count(&datagram, b"PRIORITY"), 1);
+        assert_eq!(count(&datagram, b"SYSLOG_IDENTIFIER"), 1);
+        assert!(contains(&datagram, b"MESSAGE=Original\n"));
+        assert!(contains(&datagram, b"PRIORITY=6\n"));
+        assert!(contains(&datagram, b"SYSLOG_IDENTIFIER=test-service\n"));
+    }
+
+    #[test]
+    fn encodes_multi_line_values_with_length() {
+        let (socket, path) = bind_socket();
+        let logger = logger(path.clone());
+
+        logger.send(6, "first\nsecond", &[]).unwrap();
+
+        let datagram = receive(&socket);
+        let _ = std::fs::remove_file(path);
+
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&12u64.to_le_bytes());
+        expected.extend_from_slice(b"first\nsecond\n");
+
+        assert!(contains(&datagram, expected.as_slice()));
+        assert!(!contains(&datagram, b"MESSAGE="));
+    }
+
+    #[test]
+    fn falls_back_to_stderr_when_socket_is_missing() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("journal-test-{}-missing.sock", std::process::id()));
+        let logger = logger(path);
+
+        let entry = JournalEntry::new(3, "first\nsecond", "test-service");
+        let mut output = vec![];
+        logger.write_to(&entry, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "<3>first\n<3>second\n");
+    }
+
+    #[test]
+    fn falls_back_to_stderr_when_entry_is_too_large() {
+        let (_socket, path) = bind_socket();
+        let logger = logger(path.clone());
+
+        let message = "x".repeat(16 * 1024 * 1024);
+        let entry = JournalEntry::new(6, message.as_str(), "test-service");
+        let mut output = vec![];
+        let result = logger.write_to(&entry, &mut output);
+        let _ = std::fs::remove_file(path);
+
+        result.unwrap();
+        assert_eq!(output.len(), message.len() + 4);
+        assert!(output.starts_with(b"<6>xxx"));
+    }
+}
@@ -1,2 +1,10 @@
+pub mod activation;
+#[allow(dead_code, clippy::all)]
 mod dbus_systemd1;
+pub mod journal;
+pub mod notify;
 pub mod services;
+#[cfg(feature = "tokio")]
+pub mod services_async;
+pub mod unit_file;
+pub mod watcher;
@@ -0,0 +1,116 @@
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// Name of environment variable systemd uses to pass notification socket to service
+const NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+/// Name of environment variable systemd uses to pass watchdog interval in microseconds
+const WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+/// Name of environment variable systemd uses to pass process ID expected to send keep-alive pings
+const WATCHDOG_PID: &str = "WATCHDOG_PID";
+
+/// Provides state values of [sd_notify](https://www.freedesktop.org/software/systemd/man/sd_notify.html)
+/// protocol
+pub enum NotifyState {
+    /// Tells service manager that service startup is finished (READY=1)
+    Ready,
+    /// Tells service manager that service is reloading its configuration (RELOADING=1)
+    ///
+    /// Service should send [Ready](NotifyState::Ready) when reloading is finished.
+    Reloading,
+    /// Tells service manager that service is beginning its shutdown (STOPPING=1)
+    Stopping,
+    /// Passes a single-line status string back to service manager (STATUS=...)
+    Status(String),
+    /// Tells service manager the main process ID of the service (MAINPID=...)
+    MainPid(u32),
+    /// Updates watchdog timestamp of the service (WATCHDOG=1)
+    Watchdog,
+    /// Asks service manager to extend current startup, reload or stop timeout by specified
+    /// duration (EXTEND_TIMEOUT_USEC=...)
+    ExtendTimeout(Duration),
+    /// Tells service manager that service failed with specified errno-style error code (ERRNO=...)
+    Errno(i32),
+    /// Tells service manager the exit status of the service (EXIT_STATUS=...)
+    ExitStatus(i32),
+}
+
+impl Display for NotifyState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotifyState::Ready => write!(f, "READY=1"),
+            NotifyState::Reloading => write!(f, "RELOADING=1"),
+            NotifyState::Stopping => write!(f, "STOPPING=1"),
+            NotifyState::Status(value) => write!(f, "STATUS={}", value.replace('\n', " ")),
+            NotifyState::MainPid(pid) => write!(f, "MAINPID={}", pid),
+            NotifyState::Watchdog => write!(f, "WATCHDOG=1"),
+            NotifyState::ExtendTimeout(value) => write!(f, "EXTEND_TIMEOUT_USEC={}", value.as_micros()),
+            NotifyState::Errno(value) => write!(f, "ERRNO={}", value),
+            NotifyState::ExitStatus(value) => write!(f, "EXIT_STATUS={}", value),
+        }
+    }
+}
+
+/// Sends specified states to service manager
+///
+/// Uses socket path from *NOTIFY_SOCKET* environment variable. Returns `Ok(false)` without
+/// sending anything when the variable is not set, which is the case when process was not
+/// started by systemd or unit type is not *notify*.
+pub fn notify(states: &[NotifyState]) -> Result<bool, Error> {
+    let path = match env::var_os(NOTIFY_SOCKET) {
+        None => return Ok(false),
+        Some(value) => value,
+    };
+
+    let path = path.to_str()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Invalid NOTIFY_SOCKET value"))?;
+
+    let address = socket_address(path)?;
+
+    let message = states.iter()
+        .map(|it| it.to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(message.as_bytes(), &address)?;
+
+    Ok(true)
+}
+
+/// Returns watchdog interval requested by service manager
+///
+/// Reads *WATCHDOG_USEC* environment variable, which is set by systemd when the unit has
+/// `WatchdogSec=` setting. Returns `None` when the variable is not set, or when *WATCHDOG_PID*
+/// refers to another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var(WATCHDOG_PID) {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    env::var(WATCHDOG_USEC).ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|value| *value > 0)
+        .map(Duration::from_micros)
+}
+
+#[cfg(target_os = "linux")]
+fn socket_address(path: &str) -> Result<SocketAddr, Error> {
+    use std::os::linux::net::SocketAddrExt;
+
+    // Path started with '@' refers to a socket in abstract namespace
+    if let Some(name) = path.strip_prefix('@') {
+        SocketAddr::from_abstract_name(name.as_bytes())
+    } else {
+        SocketAddr::from_pathname(path)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn socket_address(path: &str) -> Result<SocketAddr, Error> {
+    SocketAddr::from_pathname(path)
+}
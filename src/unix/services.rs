@@ -1,84 +1,605 @@
+use std::cell::{Cell, RefCell};
 use std::io::{Error, ErrorKind, Write};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use dbus::blocking::Connection;
+use dbus::blocking::{Connection, Proxy};
+use dbus::channel::Token;
+use dbus::Message;
 
-use crate::unix::dbus_systemd1::OrgFreedesktopSystemd1Manager;
-use std::fs::{OpenOptions, remove_file};
+use crate::error::ServiceError;
+use crate::unix::dbus_systemd1::{
+    OrgFreedesktopDBusProperties,
+    OrgFreedesktopSystemd1Manager,
+    OrgFreedesktopSystemd1ManagerJobRemoved as JobRemoved,
+    OrgFreedesktopSystemd1ManagerReloading as Reloading,
+};
+use crate::unix::unit_file::UnitFile;
+use std::fs::{File, OpenOptions, Permissions, read_to_string, remove_dir_all, remove_file, rename, symlink_metadata};
+use std::os::unix::fs::{fchown, OpenOptionsExt, PermissionsExt};
 use std::path::{PathBuf};
 
-pub enum Mode {
-    Replace,
-    Fail,
-    Isolate,
-    IgnoreDependencies,
-    IgnoreRequirements,
-}
-
-impl Into<&'static str> for Mode {
-    fn into(self) -> &'static str {
-        match self {
-            Mode::Replace => "replace",
-            Mode::Fail => "fail",
-            Mode::Isolate => "isolate",
-            Mode::IgnoreDependencies => "ignore-dependencies",
-            Mode::IgnoreRequirements => "ignore-requirements",
+pub use crate::service_manager::{Mode, UnitFileChange};
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+const PATH: &str = "/org/freedesktop/systemd1";
+const SERVICE_INTERFACE: &str = "org.freedesktop.systemd1.Service";
+
+/// Interval of checking whether systemd is back on the bus after it was re-executed
+const NAME_OWNER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Timeout of D-Bus calls made by functions without a timeout argument
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Provides results of a finished systemd job, reported by JobRemoved signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobResult {
+    /// Job finished successfully
+    Done,
+    /// Job was canceled before it finished
+    Canceled,
+    /// Job timed out by its unit's job timeout
+    Timeout,
+    /// Job failed, e.g. service exited with an error during startup
+    Failed,
+    /// A job this job depended on failed, so it was removed
+    Dependency,
+    /// Job was skipped because it did not apply to the unit's current state
+    Skipped,
+    /// Any other result reported by systemd
+    Other(String),
+}
+
+impl From<&str> for JobResult {
+    fn from(value: &str) -> Self {
+        match value {
+            "done" => JobResult::Done,
+            "canceled" => JobResult::Canceled,
+            "timeout" => JobResult::Timeout,
+            "failed" => JobResult::Failed,
+            "dependency" => JobResult::Dependency,
+            "skipped" => JobResult::Skipped,
+            other => JobResult::Other(other.into()),
+        }
+    }
+}
+
+/// Manages systemd units over a single system bus connection
+///
+/// Connection is reused by all operations, and is reopened once when a call fails because the
+/// bus connection dropped. When systemd is re-executed (e.g. by `systemctl daemon-reexec`), a
+/// call waits until systemd is back on the bus, up to the timeout of the manager, and is sent
+/// again.
+pub struct ServiceManager {
+    connection: RefCell<Connection>,
+    timeout: Duration,
+    auto_reload: bool,
+}
+
+impl ServiceManager {
+    /// Connects to systemd over system bus
+    ///
+    /// Specified timeout is used for every D-Bus method call.
+    pub fn new(timeout: Duration) -> Result<ServiceManager, ServiceError> {
+        let connection = Connection::new_system()
+            .map_err(from_dbus_error)?;
+
+        Ok(ServiceManager {
+            connection: RefCell::new(connection),
+            timeout,
+            auto_reload: true,
+        })
+    }
+
+    /// Returns timeout of D-Bus method calls
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets timeout of D-Bus method calls
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Starts specified unit by D-Bus StartUnit function
+    pub fn start_unit(&self, service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+        let mode: &str = mode.into();
+        self.call(|p| p.start_unit(service_name, mode))?;
+
+        Ok(())
+    }
+
+    /// Stops specified unit by D-Bus StopUnit function
+    pub fn stop_unit(&self, service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+        let mode: &str = mode.into();
+        self.call(|p| p.stop_unit(service_name, mode))?;
+
+        Ok(())
+    }
+
+    /// Starts specified unit and waits until systemd finishes the start job
+    ///
+    /// Returns [JobResult::Done] when unit started successfully. Returns a timeout error when the
+    /// job did not finish before timeout of the manager.
+    pub fn start_unit_and_wait(&self, service_name: &str, mode: Mode) -> Result<JobResult, ServiceError> {
+        let mode: &str = mode.into();
+        self.wait_job(|p| p.start_unit(service_name, mode))
+    }
+
+    /// Stops specified unit and waits until systemd finishes the stop job
+    ///
+    /// See [start_unit_and_wait](ServiceManager::start_unit_and_wait).
+    pub fn stop_unit_and_wait(&self, service_name: &str, mode: Mode) -> Result<JobResult, ServiceError> {
+        let mode: &str = mode.into();
+        self.wait_job(|p| p.stop_unit(service_name, mode))
+    }
+
+    /// Restarts specified unit by D-Bus RestartUnit function, starts the unit if it is not running
+    pub fn restart_unit(&self, service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+        let mode: &str = mode.into();
+        self.call(|p| p.restart_unit(service_name, mode))?;
+
+        Ok(())
+    }
+
+    /// Restarts specified unit by D-Bus TryRestartUnit function, does nothing if the unit is not
+    /// running
+    pub fn try_restart_unit(&self, service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+        let mode: &str = mode.into();
+        self.call(|p| p.try_restart_unit(service_name, mode))?;
+
+        Ok(())
+    }
+
+    /// Reloads configuration of specified unit by D-Bus ReloadUnit function
+    pub fn reload_unit(&self, service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+        let mode: &str = mode.into();
+        self.call(|p| p.reload_unit(service_name, mode))?;
+
+        Ok(())
+    }
+
+    /// Reloads specified unit if it supports reloading, otherwise restarts it, by D-Bus
+    /// ReloadOrRestartUnit function
+    pub fn reload_or_restart_unit(&self, service_name: &str, mode: Mode) -> Result<(), ServiceError> {
+        let mode: &str = mode.into();
+        self.call(|p| p.reload_or_restart_unit(service_name, mode))?;
+
+        Ok(())
+    }
+
+    /// Gets status of specified unit, returns `None` when unit does not exist
+    pub fn get_unit_status(&self, unit_name: &str) -> Result<Option<UnitStatus>, ServiceError> {
+        let units = self.call(|p| p.list_units_by_names(vec![unit_name]))?;
+
+        Ok(units.first().and_then(to_unit_status))
+    }
+
+    /// Gets state and main process information of specified service unit, returns `None` when
+    /// unit does not exist
+    pub fn get_service_properties(&self, unit_name: &str) -> Result<Option<ServiceProperties>, ServiceError> {
+        let units = self.call(|p| p.list_units_by_names(vec![unit_name]))?;
+
+        let unit = match units.first() {
+            Some(unit) if unit.2 != "not-found" => unit,
+            _ => return Ok(None),
+        };
+
+        let (main_pid, exec_main_code, exec_main_status) = self.call_path(unit.6.clone(), |p| {
+            Ok((p.get(SERVICE_INTERFACE, "MainPID")?,
+                p.get(SERVICE_INTERFACE, "ExecMainCode")?,
+                p.get(SERVICE_INTERFACE, "ExecMainStatus")?))
+        })?;
+
+        Ok(Some(ServiceProperties {
+            active_state: unit.3.clone(),
+            sub_state: unit.4.clone(),
+            main_pid,
+            exec_main_code,
+            exec_main_status,
+        }))
+    }
+
+    /// Disables specified unit files by D-Bus DisableUnitFiles function
+    ///
+    /// Returns symlinks removed from `[Install]` targets of the units.
+    pub fn disable_unit_files(&self, files: Vec<&str>) -> Result<Vec<UnitFileChange>, ServiceError> {
+        let changes = self.call(|p| p.disable_unit_files(files.clone(), false))?;
+
+        Ok(changes.into_iter().map(UnitFileChange::from).collect())
+    }
+
+    /// Enables specified unit files by D-Bus EnableUnitFiles function
+    ///
+    /// Returns symlinks created according to `[Install]` section of the units. Returns an error
+    /// when none of the units has an `[Install]` section, since enabling them has no effect.
+    pub fn enable_unit_files(&self, files: Vec<&str>) -> Result<Vec<UnitFileChange>, ServiceError> {
+        let (carries_install_info, changes) = self.call(|p| p.enable_unit_files(files.clone(), false, true))?;
+
+        if !carries_install_info {
+            return Err(ServiceError::InvalidArgument(
+                Error::new(ErrorKind::InvalidInput, "Unit files have no [Install] section").into()));
+        }
+
+        Ok(changes.into_iter().map(UnitFileChange::from).collect())
+    }
+
+    /// Indicate if systemd configuration is reloaded after unit files are changed
+    pub fn auto_reload(&self) -> bool {
+        self.auto_reload
+    }
+
+    /// Sets if systemd configuration is reloaded after unit files are changed, enabled by default
+    ///
+    /// Disable it to change multiple unit files, then call
+    /// [reload_daemon](ServiceManager::reload_daemon) once.
+    pub fn set_auto_reload(&mut self, auto_reload: bool) {
+        self.auto_reload = auto_reload;
+    }
+
+    /// Writes specified new unit file to path "/etc/systemd/system"
+    ///
+    /// Reloads systemd configuration if [auto_reload](ServiceManager::auto_reload) is enabled.
+    pub fn install_string(&self, file_name: &str, file_content: &str) -> Result<(), ServiceError> {
+        let file_path = unit_file_path(file_name);
+
+        if file_path.exists() {
+            return Err(ServiceError::AlreadyExists(
+                Error::new(ErrorKind::AlreadyExists, "File path already exists").into()));
+        }
+
+        write_unit_file(file_path, file_content, 0o644)?;
+        self.reload_if_needed()
+    }
+
+    /// Replaces content of specified existing unit file in path "/etc/systemd/system"
+    ///
+    /// Reloads systemd configuration if [auto_reload](ServiceManager::auto_reload) is enabled.
+    pub fn update_string(&self, file_name: &str, file_content: &str) -> Result<(), ServiceError> {
+        let file_path = unit_file_path(file_name);
+
+        if !file_path.exists() {
+            return Err(ServiceError::NotFound(
+                Error::new(ErrorKind::NotFound, "File path does not exist").into()));
+        }
+
+        write_unit_file(file_path, file_content, 0o644)?;
+        self.reload_if_needed()
+    }
+
+    /// Deletes specified unit file from path "/etc/systemd/system"
+    ///
+    /// Reloads systemd configuration if [auto_reload](ServiceManager::auto_reload) is enabled.
+    pub fn delete_unit(&self, service_name: &str) -> Result<(), ServiceError> {
+        remove_file(unit_file_path(service_name))?;
+        self.reload_if_needed()
+    }
+
+    /// Deletes drop-in directory of specified unit ("/etc/systemd/system/<unit>.d")
+    ///
+    /// Reloads systemd configuration if [auto_reload](ServiceManager::auto_reload) is enabled.
+    pub fn delete_drop_in_dir(&self, unit_name: &str) -> Result<(), ServiceError> {
+        remove_dir_all(unit_file_path(format!("{}.d", unit_name).as_str()))?;
+        self.reload_if_needed()
+    }
+
+    /// Resets failed state of specified unit by D-Bus ResetFailedUnit function
+    pub fn reset_failed_unit(&self, unit_name: &str) -> Result<(), ServiceError> {
+        self.call(|p| p.reset_failed_unit(unit_name))
+    }
+
+    /// Reloads systemd configuration by D-Bus Reload function, and waits for Reloading signal
+    /// reporting the reload is finished
+    pub fn reload_daemon(&self) -> Result<(), ServiceError> {
+        let deadline = Instant::now() + self.timeout;
+        let finished = Arc::new(AtomicBool::new(false));
+        let token = Cell::new(None);
+
+        self.call(|p| {
+            subscribe(p)?;
+
+            let cb_finished = finished.clone();
+            token.set(Some(p.match_signal(move |signal: Reloading, _: &Connection, _: &Message| {
+                if !signal.active {
+                    cb_finished.store(true, Ordering::Relaxed);
+                }
+                true
+            })?));
+
+            p.reload()
+        })?;
+
+        self.process_until(deadline, token.get(), "Reloading did not finish in time", || {
+            finished.load(Ordering::Relaxed)
+        })
+    }
+
+    fn reload_if_needed(&self) -> Result<(), ServiceError> {
+        if self.auto_reload {
+            self.reload_daemon()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Calls specified function, which queues a job, and waits for JobRemoved signal of the job
+    fn wait_job<F>(&self, f: F) -> Result<JobResult, ServiceError>
+        where F: Fn(&Proxy<&Connection>) -> Result<dbus::Path<'static>, dbus::Error> {
+        let deadline = Instant::now() + self.timeout;
+        let removed: Arc<Mutex<Vec<JobRemoved>>> = Arc::new(Mutex::new(vec![]));
+        let token = Cell::new(None);
+
+        let job = self.call(|p| {
+            // Signal is matched before queueing the job, so a job finished immediately is not missed
+            subscribe(p)?;
+
+            let cb_removed = removed.clone();
+            token.set(Some(p.match_signal(move |signal: JobRemoved, _: &Connection, _: &Message| {
+                cb_removed.lock().unwrap().push(signal);
+                true
+            })?));
+
+            f(p)
+        })?;
+
+        let mut result = None;
+        self.process_until(deadline, token.get(), "Job did not finish in time", || {
+            result = removed.lock().unwrap().iter()
+                .find(|it| it.job == job)
+                .map(|it| JobResult::from(it.result.as_str()));
+
+            result.is_some()
+        })?;
+
+        Ok(result.unwrap())
+    }
+
+    /// Processes incoming messages until specified condition is met, then removes specified match
+    fn process_until<F>(&self, deadline: Instant, token: Option<Token>, timeout_message: &str, mut condition: F)
+                        -> Result<(), ServiceError>
+        where F: FnMut() -> bool {
+        let connection = self.connection.borrow();
+
+        let result = loop {
+            if condition() {
+                break Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break Err(ServiceError::Timeout(
+                    Error::new(ErrorKind::TimedOut, timeout_message).into()));
+            }
+
+            if let Err(err) = connection.process(deadline - now) {
+                break Err(from_dbus_error(err));
+            }
+        };
+
+        if let Some(token) = token {
+            let _ = connection.remove_match(token);
+        }
+
+        result
+    }
+
+    /// Calls specified function with a proxy of systemd manager, retries once when the connection
+    /// is lost or systemd is re-executed
+    fn call<R, F>(&self, f: F) -> Result<R, ServiceError>
+        where F: Fn(&Proxy<&Connection>) -> Result<R, dbus::Error> {
+        self.call_path(PATH, f)
+    }
+
+    /// Calls specified function with a proxy of specified systemd object, retries once when the
+    /// connection is lost or systemd is re-executed
+    fn call_path<'p, R, F>(&self, path: impl Into<dbus::Path<'p>>, f: F) -> Result<R, ServiceError>
+        where F: Fn(&Proxy<&Connection>) -> Result<R, dbus::Error> {
+        let path = path.into();
+        let result = {
+            let connection = self.connection.borrow();
+            f(&connection.with_proxy(DESTINATION, path.clone(), self.timeout))
+        };
+
+        match result {
+            Err(err) if is_disconnected(&err) => {
+                let connection = Connection::new_system()
+                    .map_err(from_dbus_error)?;
+                *self.connection.borrow_mut() = connection;
+            }
+            Err(err) if is_name_lost(&err) => {
+                if !self.wait_for_systemd()? {
+                    return Err(from_dbus_error(err));
+                }
+            }
+            result => return result.map_err(from_dbus_error),
+        }
+
+        let connection = self.connection.borrow();
+        f(&connection.with_proxy(DESTINATION, path, self.timeout))
+            .map_err(from_dbus_error)
+    }
+
+    /// Waits until systemd owns its bus name again, returns false when it did not before timeout
+    /// of the manager
+    fn wait_for_systemd(&self) -> Result<bool, ServiceError> {
+        let deadline = Instant::now() + self.timeout;
+        let connection = self.connection.borrow();
+        let p = connection.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", self.timeout);
+
+        loop {
+            let (has_owner, ): (bool, ) = p.method_call("org.freedesktop.DBus", "NameHasOwner", (DESTINATION, ))
+                .map_err(from_dbus_error)?;
+
+            if has_owner {
+                return Ok(true);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+
+            std::thread::sleep(NAME_OWNER_POLL_INTERVAL.min(deadline - now));
         }
     }
 }
 
-pub fn start_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), Error> {
-    let c = Connection::new_system()
-        .map_err(|err| from_dbus_error(err))?;
+/// Subscribes to signals of systemd manager, which are not sent to unsubscribed clients
+fn subscribe(p: &Proxy<&Connection>) -> Result<(), dbus::Error> {
+    match p.subscribe() {
+        Err(err) if err.name() != Some("org.freedesktop.systemd1.AlreadySubscribed") => Err(err),
+        _ => Ok(()),
+    }
+}
 
-    let p = c.with_proxy("org.freedesktop.systemd1",
-                         "/org/freedesktop/systemd1", timeout);
-    p.start_unit(service_name, mode.into())
-        .map_err(|err| from_dbus_error(err))?;
+/// Indicate if specified error is caused by a dropped bus connection
+///
+/// *NoReply* is not included, since the call may have been executed after the timeout, and calls
+/// such as StartUnit are not safe to send again.
+fn is_disconnected(err: &dbus::Error) -> bool {
+    err.name() == Some("org.freedesktop.DBus.Error.Disconnected")
+}
 
-    Ok(())
+/// Indicate if specified error is caused by systemd not owning its bus name, e.g. while it is
+/// re-executed
+///
+/// The call was not delivered, so it is safe to send it again.
+fn is_name_lost(err: &dbus::Error) -> bool {
+    matches!(err.name(),
+        Some("org.freedesktop.DBus.Error.ServiceUnknown")
+        | Some("org.freedesktop.DBus.Error.NameHasNoOwner"))
 }
 
-pub fn stop_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), Error> {
-    let c = Connection::new_system()
-        .map_err(|err| from_dbus_error(err))?;
+pub fn start_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.start_unit(service_name, mode)
+}
 
-    let p = c.with_proxy("org.freedesktop.systemd1",
-                         "/org/freedesktop/systemd1", timeout);
+pub fn stop_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.stop_unit(service_name, mode)
+}
 
-    p.stop_unit(service_name, mode.into())
-        .map_err(|err| from_dbus_error(err))?;
+pub fn start_unit_and_wait(service_name: &str, mode: Mode, timeout: Duration) -> Result<JobResult, ServiceError> {
+    ServiceManager::new(timeout)?.start_unit_and_wait(service_name, mode)
+}
 
-    Ok(())
+pub fn stop_unit_and_wait(service_name: &str, mode: Mode, timeout: Duration) -> Result<JobResult, ServiceError> {
+    ServiceManager::new(timeout)?.stop_unit_and_wait(service_name, mode)
 }
 
-fn from_dbus_error(err: dbus::Error) -> std::io::Error {
-    std::io::Error::new(ErrorKind::Other, err.to_string())
+pub fn restart_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.restart_unit(service_name, mode)
 }
 
-pub fn install_string(file_name: &str, file_content: &str) -> Result<(), Error> {
+pub fn try_restart_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.try_restart_unit(service_name, mode)
+}
+
+pub fn reload_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.reload_unit(service_name, mode)
+}
+
+pub fn reload_or_restart_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.reload_or_restart_unit(service_name, mode)
+}
+
+pub(crate) fn from_dbus_error(err: dbus::Error) -> ServiceError {
+    ServiceError::from(err)
+}
+
+pub fn install_string(file_name: &str, file_content: &str) -> Result<(), ServiceError> {
+    ServiceManager::new(DEFAULT_TIMEOUT)?.install_string(file_name, file_content)
+}
+
+pub fn update_string(file_name: &str, file_content: &str) -> Result<(), ServiceError> {
+    ServiceManager::new(DEFAULT_TIMEOUT)?.update_string(file_name, file_content)
+}
+
+pub fn delete_unit(service_name: &str) -> Result<(), ServiceError> {
+    ServiceManager::new(DEFAULT_TIMEOUT)?.delete_unit(service_name)
+}
+
+/// Reads and parses specified unit file from path "/etc/systemd/system"
+pub fn read_unit(file_name: &str) -> Result<UnitFile, ServiceError> {
+    let content = read_to_string(unit_file_path(file_name))?;
+
+    UnitFile::parse(content.as_str())
+}
+
+pub fn reset_failed_unit(unit_name: &str, timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.reset_failed_unit(unit_name)
+}
+
+pub fn reload_daemon(timeout: Duration) -> Result<(), ServiceError> {
+    ServiceManager::new(timeout)?.reload_daemon()
+}
+
+pub(crate) fn unit_file_path(file_name: &str) -> PathBuf {
     let mut file_path = PathBuf::from("/etc/systemd/system");
     file_path.push(file_name);
 
-    if file_path.exists() {
-        Err(Error::new(ErrorKind::Other, "File path already exists"))
-    } else {
-        let mut f = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(file_path)?;
+    file_path
+}
 
-        f.write(file_content.as_bytes())?;
+/// Writes specified file to path "/etc/systemd/system", readable only by root
+///
+/// The file is written with mode 0600, replacing an existing file. Used for files read by systemd
+/// on behalf of units which must not be readable by other users, e.g. environment files containing
+/// secrets. Returns path of the written file.
+pub fn write_private_file(file_name: &str, file_content: &str) -> Result<PathBuf, ServiceError> {
+    let file_path = unit_file_path(file_name);
+    write_unit_file(file_path.clone(), file_content, 0o600)?;
 
-        Ok(())
-    }
+    Ok(file_path)
 }
 
-pub fn delete_unit(service_name: &str) -> Result<(), Error> {
-    let mut file_path = PathBuf::from("/etc/systemd/system");
-    file_path.push(service_name);
+/// Writes unit file atomically, through a temporary file in the same directory
+///
+/// The file is written with specified mode and owned by root, and is synced to disk before it
+/// replaces the destination, so a crash never leaves a truncated unit. A symlink at the
+/// destination path is refused.
+fn write_unit_file(file_path: PathBuf, file_content: &str, mode: u32) -> Result<(), ServiceError> {
+    match symlink_metadata(&file_path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            return Err(ServiceError::AccessDenied(
+                Error::new(ErrorKind::PermissionDenied, "Refusing to write unit file through a symlink").into()));
+        }
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+
+    let directory = file_path.parent()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Invalid unit file path"))?;
+    let file_name = file_path.file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Invalid unit file path"))?;
+
+    let mut temp_path = directory.to_path_buf();
+    temp_path.push(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+
+    // New file is never opened through a symlink
+    let mut f = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(&temp_path)?;
+
+    let result = (|| -> Result<(), Error> {
+        // Mode passed to open is masked by umask
+        f.set_permissions(Permissions::from_mode(mode))?;
+        fchown(&f, Some(0), Some(0))?;
+
+        f.write_all(file_content.as_bytes())?;
+        f.sync_all()?;
+
+        rename(&temp_path, &file_path)?;
+        File::open(directory)?.sync_all()
+    })();
+
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
 
-    remove_file(file_path)
+    Ok(result?)
 }
 
 pub struct UnitStatus {
@@ -90,55 +611,45 @@ pub struct UnitStatus {
     pub exec_path: String,
 }
 
-pub fn get_unit_status(unit_name: &str, timeout: Duration) -> Result<Option<UnitStatus>, Error> {
-    let c = Connection::new_system()
-        .map_err(|err| from_dbus_error(err))?;
-
-    let p = c.with_proxy("org.freedesktop.systemd1",
-                         "/org/freedesktop/systemd1", timeout);
+pub fn get_unit_status(unit_name: &str, timeout: Duration) -> Result<Option<UnitStatus>, ServiceError> {
+    ServiceManager::new(timeout)?.get_unit_status(unit_name)
+}
 
-    let units = p.list_units_by_names(vec![unit_name])
-        .map_err(|err| from_dbus_error(err))?;
+/// Provides state and main process information of a service unit
+pub struct ServiceProperties {
+    /// High-level unit state, e.g. *active*, *activating* or *failed* (ActiveState)
+    pub active_state: String,
+    /// Low-level unit state specific to service units, e.g. *running* or *auto-restart* (SubState)
+    pub sub_state: String,
+    /// Process ID of the main process, zero when service is not running (MainPID)
+    pub main_pid: u32,
+    /// How the last main process exited, *CLD_EXITED* (1) when it exited by itself (ExecMainCode)
+    pub exec_main_code: i32,
+    /// Exit status or signal number of the last main process (ExecMainStatus)
+    pub exec_main_status: i32,
+}
 
-    let unit = units.get(0)
-        .unwrap();
+/// Unit information returned by ListUnits family of D-Bus functions
+pub(crate) type UnitInfo = (String, String, String, String, String, String, dbus::Path<'static>, u32, String, dbus::Path<'static>);
 
+pub(crate) fn to_unit_status(unit: &UnitInfo) -> Option<UnitStatus> {
     if unit.2.eq("not-found") {
-        Ok(None)
+        None
     } else {
-        let result = UnitStatus {
-            name: (&unit.0).clone(),
-            description: (&unit.1).clone(),
-            is_active: (unit.3.eq("active")),
-            state: (&unit.4).to_string(),
-            exec_path: (&unit.6).to_string(),
-        };
-
-        Ok(Some(result))
+        Some(UnitStatus {
+            name: unit.0.clone(),
+            description: unit.1.clone(),
+            is_active: unit.3.eq("active"),
+            state: unit.4.clone(),
+            exec_path: unit.6.to_string(),
+        })
     }
-    // Ok(())
 }
 
-pub fn disable_unit_files(files: Vec<&str>, timeout: Duration) -> Result<(), Error> {
-    let c = Connection::new_system()
-        .map_err(|err| from_dbus_error(err))?;
-
-    let p = c.with_proxy("org.freedesktop.systemd1",
-                         "/org/freedesktop/systemd1", timeout);
-    p.disable_unit_files(files, true)
-        .map_err(|err| from_dbus_error(err))?;
-
-    Ok(())
+pub fn disable_unit_files(files: Vec<&str>, timeout: Duration) -> Result<Vec<UnitFileChange>, ServiceError> {
+    ServiceManager::new(timeout)?.disable_unit_files(files)
 }
 
-pub fn enable_unit_files(files: Vec<&str>, timeout: Duration) -> Result<(), Error> {
-    let c = Connection::new_system()
-        .map_err(|err| from_dbus_error(err))?;
-
-    let p = c.with_proxy("org.freedesktop.systemd1",
-                         "/org/freedesktop/systemd1", timeout);
-    p.enable_unit_files(files, true, true)
-        .map_err(|err| from_dbus_error(err))?;
-
-    Ok(())
-}
\ No newline at end of file
+pub fn enable_unit_files(files: Vec<&str>, timeout: Duration) -> Result<Vec<UnitFileChange>, ServiceError> {
+    ServiceManager::new(timeout)?.enable_unit_files(files)
+}
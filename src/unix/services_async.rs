@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dbus::arg::{AppendAll, ReadAll};
+use dbus::nonblock::{Proxy, SyncConnection};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use tokio::task::JoinHandle;
+
+use crate::error::ServiceError;
+use crate::unix::services::{from_dbus_error, Mode, ServiceProperties, to_unit_status, UnitInfo, UnitStatus};
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+const PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const SERVICE_INTERFACE: &str = "org.freedesktop.systemd1.Service";
+
+/// Starts specified unit without blocking current thread
+///
+/// Async version of [start_unit](crate::unix::services::start_unit). Must be called within a
+/// tokio runtime.
+pub async fn start_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    let mode: &str = mode.into();
+    let _: (dbus::Path<'static>, ) = call_manager("StartUnit", (service_name, mode), timeout).await?;
+
+    Ok(())
+}
+
+/// Stops specified unit without blocking current thread
+///
+/// Async version of [stop_unit](crate::unix::services::stop_unit). Must be called within a
+/// tokio runtime.
+pub async fn stop_unit(service_name: &str, mode: Mode, timeout: Duration) -> Result<(), ServiceError> {
+    let mode: &str = mode.into();
+    let _: (dbus::Path<'static>, ) = call_manager("StopUnit", (service_name, mode), timeout).await?;
+
+    Ok(())
+}
+
+/// Gets status of specified unit without blocking current thread
+///
+/// Async version of [get_unit_status](crate::unix::services::get_unit_status). Must be called
+/// within a tokio runtime.
+pub async fn get_unit_status(unit_name: &str, timeout: Duration) -> Result<Option<UnitStatus>, ServiceError> {
+    let (units, ): (Vec<UnitInfo>, ) = call_manager("ListUnitsByNames",
+                                                    (vec![unit_name], ), timeout).await?;
+
+    Ok(units.first().and_then(to_unit_status))
+}
+
+/// Gets state and main process information of specified service unit without blocking current
+/// thread, returns `None` when unit does not exist
+///
+/// Async version of [get_service_properties](crate::unix::services::ServiceManager::get_service_properties).
+/// Must be called within a tokio runtime.
+pub async fn get_service_properties(unit_name: &str, timeout: Duration)
+                                    -> Result<Option<ServiceProperties>, ServiceError> {
+    let (connection, handle) = connect()?;
+    let result = read_service_properties(connection, unit_name, timeout).await;
+
+    handle.abort();
+
+    result
+}
+
+async fn read_service_properties(connection: Arc<SyncConnection>, unit_name: &str, timeout: Duration)
+                                 -> Result<Option<ServiceProperties>, ServiceError> {
+    let p = Proxy::new(DESTINATION, PATH, timeout, connection.clone());
+    let (units, ): (Vec<UnitInfo>, ) = p.method_call(MANAGER_INTERFACE, "ListUnitsByNames", (vec![unit_name], )).await
+        .map_err(from_dbus_error)?;
+
+    let unit = match units.first() {
+        Some(unit) if unit.2 != "not-found" => unit,
+        _ => return Ok(None),
+    };
+
+    let p = Proxy::new(DESTINATION, unit.6.clone(), timeout, connection);
+
+    Ok(Some(ServiceProperties {
+        active_state: unit.3.clone(),
+        sub_state: unit.4.clone(),
+        main_pid: p.get(SERVICE_INTERFACE, "MainPID").await.map_err(from_dbus_error)?,
+        exec_main_code: p.get(SERVICE_INTERFACE, "ExecMainCode").await.map_err(from_dbus_error)?,
+        exec_main_status: p.get(SERVICE_INTERFACE, "ExecMainStatus").await.map_err(from_dbus_error)?,
+    }))
+}
+
+/// Calls specified method of systemd manager over a new system bus connection
+async fn call_manager<A, R>(method: &str, args: A, timeout: Duration) -> Result<R, ServiceError>
+    where A: AppendAll, R: ReadAll + 'static {
+    let (connection, handle) = connect()?;
+
+    let p = Proxy::new(DESTINATION, PATH, timeout, connection);
+    let result = p.method_call(MANAGER_INTERFACE, method, args).await
+        .map_err(from_dbus_error);
+
+    handle.abort();
+
+    result
+}
+
+/// Connects to system bus and drives the connection in a spawned task
+fn connect() -> Result<(Arc<SyncConnection>, JoinHandle<()>), ServiceError> {
+    let (resource, connection) = dbus_tokio::connection::new_system_sync()
+        .map_err(from_dbus_error)?;
+
+    let handle = tokio::spawn(async {
+        let _ = resource.await;
+    });
+
+    Ok((connection, handle))
+}
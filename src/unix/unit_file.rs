@@ -0,0 +1,879 @@
+use std::fmt::Write;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::error::ServiceError;
+
+/// Provides content of a systemd unit file
+///
+/// Unit file consists of sections, each containing lines in the order they are written. Keys may
+/// be repeated, which is how systemd settings such as `ExecStartPre=` or `Environment=` are
+/// written multiple times. Values are stored as written in the file, so they are escaped by the
+/// functions adding them. Lines of a parsed file keep their original text, so the file is written
+/// back byte for byte, except the lines which are added or replaced.
+///
+/// ```
+/// use cross_platform_service::unix::unit_file::UnitFile;
+///
+/// let mut unit = UnitFile::new();
+/// unit.section_mut("Unit").add("Description", "My service");
+/// unit.section_mut("Service").add_command("ExecStart", &["/opt/my service/run"]);
+///
+/// assert_eq!(unit.serialize().unwrap(),
+///            "[Unit]\nDescription=My service\n\n[Service]\nExecStart=\"/opt/my service/run\"\n");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnitFile {
+    preamble: Vec<SourceLine>,
+    sections: Vec<Section>,
+    /// Indicate if parsed content does not end with a new line
+    missing_final_newline: bool,
+}
+
+/// Provides a section of a unit file, e.g. `[Service]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    name: String,
+    /// Original text of the section header, when it is parsed
+    header: Option<String>,
+    lines: Vec<SourceLine>,
+}
+
+/// Provides a line of a unit file section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    /// Assignment of a value to a key, value is stored as written in the file
+    Entry(String, String),
+    /// Comment line, including its leading `#` or `;` character
+    Comment(String),
+    /// Empty line
+    Empty,
+}
+
+/// A line with its original text, when it is parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SourceLine {
+    line: Line,
+    text: Option<String>,
+}
+
+impl From<Line> for SourceLine {
+    fn from(line: Line) -> Self {
+        SourceLine { line, text: None }
+    }
+}
+
+impl UnitFile {
+    /// Creates an empty unit file
+    pub fn new() -> UnitFile {
+        UnitFile::default()
+    }
+
+    /// Parses content of a unit file
+    ///
+    /// Handles comments, continuation lines (a line ending with a backslash) and repeated keys.
+    /// Lines before the first section may only be comments or empty lines. Original text of the
+    /// lines is kept, so [serialize](UnitFile::serialize) returns the same content until the file
+    /// is changed.
+    ///
+    /// ```
+    /// use cross_platform_service::unix::unit_file::UnitFile;
+    ///
+    /// let content = "[Service]\nExecStart=/usr/bin/my-service \\\n  --verbose\n";
+    /// let mut unit = UnitFile::parse(content).unwrap();
+    ///
+    /// let service = unit.section_mut("Service");
+    /// assert_eq!(service.get_command("ExecStart").unwrap().unwrap(),
+    ///            vec!["/usr/bin/my-service", "--verbose"]);
+    ///
+    /// service.set("Restart", "always");
+    /// assert_eq!(unit.serialize().unwrap(), format!("{}Restart=always\n", content));
+    /// ```
+    pub fn parse(content: &str) -> Result<UnitFile, ServiceError> {
+        let mut unit = UnitFile::new();
+        let mut pieces: Vec<&str> = content.split('\n').collect();
+
+        // Content ending with a new line has an empty piece after it
+        if pieces.last() == Some(&"") {
+            pieces.pop();
+        } else {
+            unit.missing_final_newline = !content.is_empty();
+        }
+
+        let mut lines = pieces.into_iter().enumerate();
+
+        while let Some((number, raw)) = lines.next() {
+            let trimmed = raw.trim();
+            let mut text = raw.to_string();
+
+            let line = if trimmed.is_empty() {
+                Line::Empty
+            } else if is_comment(trimmed) {
+                Line::Comment(trimmed.into())
+            } else if trimmed.starts_with('[') {
+                if !trimmed.ends_with(']') || trimmed.len() < 3 {
+                    return Err(invalid(format!("Invalid section header at line {}", number + 1)));
+                }
+
+                let mut section = Section::new(&trimmed[1..trimmed.len() - 1]);
+                section.header = Some(text);
+                unit.sections.push(section);
+                continue;
+            } else {
+                let (key, value) = match trimmed.find('=') {
+                    Some(index) => (trimmed[..index].trim_end(), trimmed[index + 1..].trim_start()),
+                    None => return Err(invalid(format!("Missing '=' at line {}", number + 1))),
+                };
+
+                let mut value = value.to_string();
+
+                let mut continued = is_continued(value.as_str());
+
+                // Comment lines within continuation lines are ignored by systemd, but are kept here
+                while continued {
+                    let (_, next) = match lines.next() {
+                        Some(next) => next,
+                        None => break,
+                    };
+
+                    value.push('\n');
+                    value.push_str(next.trim_end());
+                    text.push('\n');
+                    text.push_str(next);
+
+                    if !is_comment(next) {
+                        continued = is_continued(next.trim_end());
+                    }
+                }
+
+                Line::Entry(key.into(), value)
+            };
+
+            let line = SourceLine { line, text: Some(text) };
+
+            match unit.sections.last_mut() {
+                Some(section) => section.lines.push(line),
+                None if matches!(line.line, Line::Entry(..)) => {
+                    return Err(invalid(format!("Assignment outside of section at line {}", number + 1)));
+                }
+                None => unit.preamble.push(line),
+            }
+        }
+
+        Ok(unit)
+    }
+
+    /// Returns sections of the unit file
+    pub fn sections(&self) -> &[Section] {
+        self.sections.as_slice()
+    }
+
+    /// Returns first section with specified name
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|it| it.name == name)
+    }
+
+    /// Returns first section with specified name, the section is appended if it does not exist
+    pub fn section_mut(&mut self, name: &str) -> &mut Section {
+        let index = match self.sections.iter().position(|it| it.name == name) {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(name));
+                self.sections.len() - 1
+            }
+        };
+
+        &mut self.sections[index]
+    }
+
+    /// Removes all sections with specified name
+    pub fn remove_section(&mut self, name: &str) {
+        self.sections.retain(|it| it.name != name);
+    }
+
+    /// Writes unit file content
+    ///
+    /// Parsed lines are written with their original text. Added sections are separated by an empty
+    /// line. Returns an error when a section name, key or value is not valid, e.g. a value contains
+    /// a new line or other control characters.
+    pub fn serialize(&self) -> Result<String, ServiceError> {
+        let mut content = String::new();
+
+        for line in self.preamble.iter() {
+            write_source_line(&mut content, line)?;
+        }
+
+        for section in self.sections.iter() {
+            match section.header.as_ref() {
+                Some(header) => {
+                    content.push_str(header);
+                    content.push('\n');
+                }
+                None => {
+                    if !content.is_empty() && !content.ends_with("\n\n") {
+                        content.push('\n');
+                    }
+
+                    if section.name.is_empty()
+                        || section.name.chars().any(|it| it == '[' || it == ']' || it.is_control()) {
+                        return Err(invalid(format!("Invalid section name '{}'", section.name)));
+                    }
+
+                    let _ = writeln!(content, "[{}]", section.name);
+                }
+            }
+
+            for line in section.lines.iter() {
+                write_source_line(&mut content, line)?;
+            }
+        }
+
+        if self.missing_final_newline && content.ends_with('\n') {
+            content.pop();
+        }
+
+        Ok(content)
+    }
+}
+
+impl FromStr for UnitFile {
+    type Err = ServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UnitFile::parse(s)
+    }
+}
+
+impl Section {
+    /// Creates an empty section with specified name
+    pub fn new(name: &str) -> Section {
+        Section {
+            name: name.into(),
+            header: None,
+            lines: vec![],
+        }
+    }
+
+    /// Returns name of the section
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Returns lines of the section
+    pub fn lines(&self) -> impl Iterator<Item=&Line> {
+        self.lines.iter().map(|it| &it.line)
+    }
+
+    /// Returns entries of the section as key and value pairs, in the order they are written
+    pub fn entries(&self) -> impl Iterator<Item=(&str, &str)> {
+        self.lines().filter_map(|line| match line {
+            Line::Entry(key, value) => Some((key.as_str(), value.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Appends an entry, specifiers are escaped so the value is used as is
+    ///
+    /// Percent sign is written as `%%`, so systemd does not expand it as a specifier.
+    pub fn add(&mut self, key: &str, value: &str) -> &mut Section {
+        self.add_raw(key, value.replace('%', "%%").as_str())
+    }
+
+    /// Appends an entry with a value written without escaping
+    ///
+    /// Use it for values containing specifiers (e.g. `%i`) or variables (e.g. `$MAINPID`).
+    pub fn add_raw(&mut self, key: &str, value: &str) -> &mut Section {
+        self.lines.push(Line::Entry(key.into(), value.into()).into());
+        self
+    }
+
+    /// Appends a command line entry, e.g. `ExecStart=`, from specified program and arguments
+    ///
+    /// Each word is quoted when needed, and specifiers and variables are escaped, so systemd
+    /// passes the words to the program as they are.
+    pub fn add_command(&mut self, key: &str, words: &[&str]) -> &mut Section {
+        self.add_raw(key, quote_command(words).as_str())
+    }
+
+    /// Appends an `Environment=` entry setting specified variable
+    ///
+    /// The assignment is enclosed in double quotes, and quotes, backslashes, control characters
+    /// and specifiers are escaped. Returns an error if the variable name is not valid.
+    pub fn add_environment(&mut self, name: &str, value: &str) -> Result<&mut Section, ServiceError> {
+        let value = quote_environment(name, value)?;
+        Ok(self.add_raw("Environment", value.as_str()))
+    }
+
+    /// Appends a comment line, `#` is added if comment does not start with `#` or `;`
+    pub fn add_comment(&mut self, comment: &str) -> &mut Section {
+        if comment.starts_with('#') || comment.starts_with(';') {
+            self.lines.push(Line::Comment(comment.into()).into());
+        } else {
+            self.lines.push(Line::Comment(format!("# {}", comment)).into());
+        }
+
+        self
+    }
+
+    /// Removes all entries of specified key
+    pub fn remove(&mut self, key: &str) -> &mut Section {
+        self.lines.retain(|it| !matches!(&it.line, Line::Entry(it, _) if it == key));
+        self
+    }
+
+    /// Replaces all entries of specified key by a single entry, specifiers are escaped
+    ///
+    /// The entry is written in place of the first removed entry, or appended if the key does not
+    /// exist.
+    pub fn set(&mut self, key: &str, value: &str) -> &mut Section {
+        let value = value.replace('%', "%%");
+
+        let index = self.lines().position(|line| matches!(line, Line::Entry(it, _) if it == key));
+
+        match index {
+            Some(index) => {
+                self.remove(key);
+                self.lines.insert(index, Line::Entry(key.into(), value).into());
+                self
+            }
+            None => self.add_raw(key, value.as_str()),
+        }
+    }
+
+    /// Returns last value of specified key, as written in the file
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .filter(|(it, _)| *it == key)
+            .map(|(_, value)| value)
+            .last()
+    }
+
+    /// Returns values of specified key, with continuation lines joined and comments removed
+    ///
+    /// An empty assignment resets the list of values, as systemd does for list settings, so only
+    /// values after the last empty assignment are returned.
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        let mut values = vec![];
+
+        for (_, value) in self.entries().filter(|(it, _)| *it == key) {
+            let value = unfold(value);
+
+            if value.is_empty() {
+                values.clear();
+            } else {
+                values.push(value);
+            }
+        }
+
+        values
+    }
+
+    /// Returns last command line of specified key, split into words
+    ///
+    /// Quotes and escapes are removed, and `%%` and `$$` are replaced by `%` and `$`, reverse of
+    /// [add_command](Section::add_command). Returns an error when quotes are not closed.
+    pub fn get_command(&self, key: &str) -> Option<Result<Vec<String>, ServiceError>> {
+        self.get(key).map(|value| split_command(unfold(value).as_str()))
+    }
+}
+
+/// Quotes an assignment of a variable for `Environment=` setting
+///
+/// ```
+/// use cross_platform_service::unix::unit_file::quote_environment;
+///
+/// assert_eq!(quote_environment("GREETING", "say \"hi\"\n100%").unwrap(),
+///            r#""GREETING=say \"hi\"\n100%%""#);
+/// ```
+pub fn quote_environment(name: &str, value: &str) -> Result<String, ServiceError> {
+    check_environment_name(name)?;
+
+    let mut quoted = format!("\"{}=", name);
+
+    for c in value.chars() {
+        match c {
+            '\0' => return Err(invalid(format!("Value of variable '{}' contains null character", name))),
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_ascii_control() => {
+                let _ = write!(quoted, "\\x{:02x}", c as u8);
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+
+    Ok(quoted)
+}
+
+/// Writes content of an environment file, read by `EnvironmentFile=` setting
+///
+/// Each variable is written as `NAME="value"` line, where quotes, backslashes, `$` and backquotes
+/// are escaped by backslash. New lines are kept within the quotes.
+///
+/// ```
+/// use cross_platform_service::unix::unit_file::environment_file;
+///
+/// let content = environment_file(&[("TOKEN".into(), "a$b\"c".into())]).unwrap();
+/// assert_eq!(content, "TOKEN=\"a\\$b\\\"c\"\n");
+/// ```
+pub fn environment_file(variables: &[(String, String)]) -> Result<String, ServiceError> {
+    let mut content = String::new();
+
+    for (name, value) in variables {
+        check_environment_name(name)?;
+
+        if value.contains('\0') {
+            return Err(invalid(format!("Value of variable '{}' contains null character", name)));
+        }
+
+        let _ = write!(content, "{}=\"", name);
+
+        for c in value.chars() {
+            if c == '"' || c == '\\' || c == '$' || c == '`' {
+                content.push('\\');
+            }
+            content.push(c);
+        }
+
+        content.push_str("\"\n");
+    }
+
+    Ok(content)
+}
+
+/// Returns an error if specified environment variable name is not valid
+///
+/// Valid names contain ASCII letters, digits and underscores, and do not start with a digit.
+fn check_environment_name(name: &str) -> Result<(), ServiceError> {
+    let valid = name.chars().next().is_some_and(|it| !it.is_ascii_digit())
+        && name.chars().all(|it| it.is_ascii_alphanumeric() || it == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid(format!("Invalid environment variable name '{}'", name)))
+    }
+}
+
+/// Returns true if specified line ends with a backslash which is not escaped by another backslash
+fn is_continued(line: &str) -> bool {
+    line.chars().rev().take_while(|it| *it == '\\').count() % 2 == 1
+}
+
+/// Returns true if specified line is a comment line
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('#') || line.starts_with(';')
+}
+
+/// Joins continuation lines of a value, as systemd does
+///
+/// Backslash at the end of a line is replaced by a space, comment lines are skipped and
+/// whitespace around lines is removed.
+fn unfold(value: &str) -> String {
+    let mut unfolded = String::new();
+
+    for (index, line) in value.split('\n').enumerate() {
+        let line = line.trim();
+
+        if index > 0 && is_comment(line) {
+            continue;
+        }
+
+        if is_continued(line) {
+            unfolded.push_str(&line[..line.len() - 1]);
+            unfolded.push(' ');
+        } else {
+            unfolded.push_str(line);
+        }
+    }
+
+    unfolded.trim().into()
+}
+
+/// Splits a command line into words, as systemd does for `ExecStart=` and similar settings
+fn split_command(value: &str) -> Result<Vec<String>, ServiceError> {
+    let mut words = vec![];
+    let mut chars = value.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|it| it.is_whitespace()) {
+            chars.next();
+        }
+
+        if chars.peek().is_none() {
+            return Ok(words);
+        }
+
+        let mut word = String::new();
+        let mut quote = None;
+
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', _) => match chars.next() {
+                    Some('n') => word.push('\n'),
+                    Some('t') => word.push('\t'),
+                    Some('r') => word.push('\r'),
+                    Some('s') => word.push(' '),
+                    Some(c) => word.push(c),
+                    None => return Err(invalid("Command line ends with backslash".into())),
+                },
+                ('"', None) | ('\'', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                (c, None) if c.is_whitespace() => break,
+                ('%', _) | ('$', _) if chars.peek() == Some(&c) => {
+                    chars.next();
+                    word.push(c);
+                }
+                (c, _) => word.push(c),
+            }
+        }
+
+        if quote.is_some() {
+            return Err(invalid("Command line has unclosed quote".into()));
+        }
+
+        words.push(word);
+    }
+}
+
+/// Builds a command line for `ExecStart=` and similar settings from specified words
+///
+/// Each word is quoted by [quote_command_word], so systemd splits the command line into the same
+/// words.
+///
+/// ```
+/// use cross_platform_service::unix::unit_file::quote_command;
+///
+/// assert_eq!(quote_command(&["/opt/my app/run", "--name", "50% $HOME", ""]),
+///            "\"/opt/my app/run\" --name \"50%% $$HOME\" \"\"");
+/// ```
+pub fn quote_command(words: &[&str]) -> String {
+    words.iter()
+        .map(|it| quote_command_word(it))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Quotes a word of a command line, so systemd splits it as a single word
+///
+/// Words containing whitespace, quotes or backslashes are enclosed in double quotes, and quotes
+/// and backslashes are escaped by backslash. `%` and `$` are doubled, so they are not expanded as
+/// specifiers or environment variables.
+pub fn quote_command_word(word: &str) -> String {
+    let word = word.replace('%', "%%").replace('$', "$$");
+
+    let needs_quote = word.is_empty()
+        || word.chars().any(|it| it.is_whitespace() || it == '"' || it == '\'' || it == '\\' || it == ';');
+
+    if !needs_quote {
+        return word;
+    }
+
+    let mut quoted = String::with_capacity(word.len() + 2);
+    quoted.push('"');
+    for c in word.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Writes original text of a parsed line, or serializes an added line
+fn write_source_line(content: &mut String, line: &SourceLine) -> Result<(), ServiceError> {
+    match line.text.as_ref() {
+        Some(text) => {
+            content.push_str(text);
+            content.push('\n');
+            Ok(())
+        }
+        None => write_line(content, &line.line),
+    }
+}
+
+fn write_line(content: &mut String, line: &Line) -> Result<(), ServiceError> {
+    match line {
+        Line::Entry(key, value) => {
+            if key.is_empty()
+                || !key.chars().all(|it| it.is_ascii_alphanumeric() || it == '-' || it == '_' || it == '.') {
+                return Err(invalid(format!("Invalid key '{}'", key)));
+            }
+
+            if !is_valid_value(value) {
+                return Err(invalid(format!("Value of '{}' contains control characters", key)));
+            }
+
+            // Backslash at the end of line continues the value on next line
+            if is_continued(value) {
+                return Err(invalid(format!("Value of '{}' ends with backslash", key)));
+            }
+
+            let _ = writeln!(content, "{}={}", key, value);
+        }
+        Line::Comment(comment) => {
+            if comment.chars().any(|it| it.is_control()) {
+                return Err(invalid("Comment contains control characters".into()));
+            }
+
+            let _ = writeln!(content, "{}", comment);
+        }
+        Line::Empty => content.push('\n'),
+    }
+
+    Ok(())
+}
+
+/// Returns true if specified value has no control characters other than tab
+///
+/// New line is only accepted as part of a continuation, after a line ending with backslash or a
+/// comment line within the continuation, so it can not start a new entry or section.
+fn is_valid_value(value: &str) -> bool {
+    let lines: Vec<&str> = value.split('\n').collect();
+
+    lines.iter().enumerate().all(|(index, line)| {
+        let valid_end = if index == lines.len() - 1 {
+            index == 0 || !is_comment(line)
+        } else {
+            is_continued(line) || (index > 0 && is_comment(line))
+        };
+
+        valid_end && !line.chars().any(|it| it.is_control() && it != '\t')
+    })
+}
+
+fn invalid(message: String) -> ServiceError {
+    ServiceError::InvalidArgument(Error::new(ErrorKind::InvalidInput, message).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: &str = "# Installed by my-service\r\n\
+                        [Unit]\r\n\
+                        Description = My service\r\n\
+                        After=network.target\r\n\
+                        [Service]\r\n\
+                        ; started by the package\r\n\
+                        \x20   ExecStart=/usr/bin/my-service \\\r\n\
+                        \x20       --verbose \\\r\n\
+                        # --debug \\\r\n\
+                        \x20       --port 8080\r\n\
+                        Environment=\"A=1\"\r\n\
+                        \r\n\
+                        \r\n\
+                        [Install]\r\n\
+                        WantedBy=multi-user.target";
+
+    #[test]
+    fn round_trips_byte_for_byte() {
+        let unit = UnitFile::parse(UNIT).unwrap();
+
+        assert_eq!(unit.serialize().unwrap(), UNIT);
+        assert_eq!(UnitFile::parse("").unwrap().serialize().unwrap(), "");
+        assert_eq!(UnitFile::parse("\n\n[A]\nX=1\n").unwrap().serialize().unwrap(), "\n\n[A]\nX=1\n");
+    }
+
+    #[test]
+    fn writes_changed_lines_only() {
+        let mut unit = UnitFile::parse("[Service]\n  Type = simple\nRestart=no  \n").unwrap();
+        unit.section_mut("Service").set("Restart", "always");
+        unit.section_mut("Install").add("WantedBy", "multi-user.target");
+
+        assert_eq!(unit.serialize().unwrap(),
+                   "[Service]\n  Type = simple\nRestart=always\n\n[Install]\nWantedBy=multi-user.target\n");
+    }
+
+    #[test]
+    fn parses_continuations() {
+        let unit = UnitFile::parse(UNIT).unwrap();
+        let service = unit.section("Service").unwrap();
+
+        assert_eq!(service.get("ExecStart").unwrap(),
+                   "/usr/bin/my-service \\\n        --verbose \\\n# --debug \\\n        --port 8080");
+        assert_eq!(service.get_all("ExecStart"),
+                   vec!["/usr/bin/my-service  --verbose  --port 8080"]);
+        assert_eq!(service.get_command("ExecStart").unwrap().unwrap(),
+                   vec!["/usr/bin/my-service", "--verbose", "--port", "8080"]);
+        assert_eq!(unit.section("Unit").unwrap().get("Description"), Some("My service"));
+        assert_eq!(unit.section("Install").unwrap().get("WantedBy"), Some("multi-user.target"));
+    }
+
+    #[test]
+    fn continuation_at_end_of_file() {
+        let unit = UnitFile::parse("[Service]\nExecStart=/bin/true \\").unwrap();
+
+        assert_eq!(unit.section("Service").unwrap().get_all("ExecStart"), vec!["/bin/true"]);
+        assert_eq!(unit.serialize().unwrap(), "[Service]\nExecStart=/bin/true \\");
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let unit = UnitFile::parse(UNIT).unwrap();
+
+        assert_eq!(unit.section("Service").unwrap().lines().next(),
+                   Some(&Line::Comment("; started by the package".into())));
+        assert!(unit.serialize().unwrap().starts_with("# Installed by my-service\r\n[Unit]"));
+    }
+
+    #[test]
+    fn empty_assignment_resets_list() {
+        let unit = UnitFile::parse("[Service]\nExecStartPre=/bin/a\nExecStartPre=\n\
+                                    ExecStartPre=/bin/b\nExecStartPre=/bin/c\n").unwrap();
+        let service = unit.section("Service").unwrap();
+
+        assert_eq!(service.get_all("ExecStartPre"), vec!["/bin/b", "/bin/c"]);
+        assert_eq!(service.get_all("ExecStop"), Vec::<String>::new());
+
+        let unit = UnitFile::parse("[Service]\nExecStartPre=/bin/a\nExecStartPre=\n").unwrap();
+        assert!(unit.section("Service").unwrap().get_all("ExecStartPre").is_empty());
+    }
+
+    fn serialize_description(description: &str) -> Result<String, ServiceError> {
+        let mut unit = UnitFile::new();
+        unit.section_mut("Unit").add("Description", description);
+        unit.serialize()
+    }
+
+    #[test]
+    fn rejects_new_line_in_value() {
+        assert!(serialize_description("My service\n[Service]").is_err());
+        assert!(serialize_description("My service\r\nExecStart=/bin/sh").is_err());
+        assert!(serialize_description("My service\n").is_err());
+    }
+
+    #[test]
+    fn rejects_control_characters_in_value() {
+        assert!(serialize_description("My\x07service").is_err());
+        assert!(serialize_description("My\x1bservice").is_err());
+        assert!(serialize_description("My\0service").is_err());
+        assert_eq!(serialize_description("My\tservice").unwrap(), "[Unit]\nDescription=My\tservice\n");
+    }
+
+    #[test]
+    fn rejects_trailing_backslash() {
+        assert!(serialize_description("My service\\").is_err());
+        assert!(serialize_description("My service\\\\\\").is_err());
+        assert_eq!(serialize_description("My service\\\\").unwrap(),
+                   "[Unit]\nDescription=My service\\\\\n");
+    }
+
+    #[test]
+    fn rejects_invalid_keys_and_section_names() {
+        let mut unit = UnitFile::new();
+        unit.section_mut("Unit").add("Descri ption", "My service");
+        assert!(unit.serialize().is_err());
+
+        let mut unit = UnitFile::new();
+        unit.section_mut("Unit]\n[Service").add("Description", "My service");
+        assert!(unit.serialize().is_err());
+    }
+
+    #[test]
+    fn escapes_specifiers_in_add_only() {
+        let mut unit = UnitFile::new();
+        unit.section_mut("Service")
+            .add("Description", "100% of %n")
+            .add_raw("ExecStart", "/usr/bin/my-service %i")
+            .set("WorkingDirectory", "/srv/%u");
+
+        assert_eq!(unit.serialize().unwrap(),
+                   "[Service]\nDescription=100%% of %%n\nExecStart=/usr/bin/my-service %i\n\
+                    WorkingDirectory=/srv/%%u\n");
+    }
+
+    #[test]
+    fn keeps_order_of_repeated_keys() {
+        let mut unit = UnitFile::new();
+        unit.section_mut("Service")
+            .add("ExecStartPre", "/bin/a")
+            .add("Type", "simple")
+            .add("ExecStartPre", "/bin/b")
+            .add("ExecStartPre", "/bin/c");
+        unit.section_mut("Install").add("WantedBy", "a.target").add("WantedBy", "b.target");
+
+        assert_eq!(unit.serialize().unwrap(),
+                   "[Service]\nExecStartPre=/bin/a\nType=simple\nExecStartPre=/bin/b\n\
+                    ExecStartPre=/bin/c\n\n[Install]\nWantedBy=a.target\nWantedBy=b.target\n");
+        assert_eq!(unit.section("Service").unwrap().get_all("ExecStartPre"),
+                   vec!["/bin/a", "/bin/b", "/bin/c"]);
+
+        unit.section_mut("Service").set("ExecStartPre", "/bin/d");
+        assert_eq!(unit.section("Service").unwrap().entries().collect::<Vec<_>>(),
+                   vec![("ExecStartPre", "/bin/d"), ("Type", "simple")]);
+    }
+
+    #[test]
+    fn keeps_plain_command_words() {
+        assert_eq!(quote_command_word("--verbose"), "--verbose");
+        assert_eq!(quote_command_word("/usr/bin/my-service"), "/usr/bin/my-service");
+    }
+
+    #[test]
+    fn quotes_empty_words_and_whitespace() {
+        assert_eq!(quote_command_word(""), r#""""#);
+        assert_eq!(quote_command_word("My App"), r#""My App""#);
+        assert_eq!(quote_command_word("a\tb"), "\"a\tb\"");
+        assert_eq!(quote_command_word("a;b"), r#""a;b""#);
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes() {
+        assert_eq!(quote_command_word(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_command_word("it's"), r#""it's""#);
+        assert_eq!(quote_command_word(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(quote_command_word(r"C:\data\"), r#""C:\\data\\""#);
+        assert_eq!(quote_command_word(r"trailing\\"), r#""trailing\\\\""#);
+    }
+
+    #[test]
+    fn escapes_specifiers_and_variables() {
+        assert_eq!(quote_command_word("100%"), "100%%");
+        assert_eq!(quote_command_word("$HOME"), "$$HOME");
+        assert_eq!(quote_command_word("${A} %n"), r#""$${A} %%n""#);
+        assert_eq!(quote_command(&["/bin/echo", "", "$PATH", "50% off"]),
+                   r#"/bin/echo "" $$PATH "50%% off""#);
+    }
+
+    #[test]
+    fn round_trips_through_split_command() {
+        let words = ["/usr/bin/my-service", "", " ", "My App", r#"say "hi""#, "it's", r#"a\"b"#,
+                     r"C:\data\", r"\\", "100%", "%%n", "$HOME", "$$", "a;b", "a\tb", "#", "--"];
+        let command = quote_command(&words);
+
+        assert_eq!(split_command(command.as_str()).unwrap(), words);
+
+        let mut unit = UnitFile::new();
+        unit.section_mut("Service").add_command("ExecStart", &words);
+        let unit = UnitFile::parse(unit.serialize().unwrap().as_str()).unwrap();
+
+        assert_eq!(unit.section("Service").unwrap().get_command("ExecStart").unwrap().unwrap(), words);
+    }
+
+    #[test]
+    fn rejects_unclosed_quotes_and_trailing_backslash() {
+        assert!(split_command(r#"/bin/echo "a"#).is_err());
+        assert!(split_command("/bin/echo 'a").is_err());
+        assert!(split_command(r"/bin/echo a\").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!(UnitFile::parse("Description=My service\n").is_err());
+        assert!(UnitFile::parse("[Unit]\nDescription\n").is_err());
+        assert!(UnitFile::parse("[Unit\n").is_err());
+        assert!(UnitFile::parse("[]\n").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dbus::arg::prop_cast;
+use dbus::blocking::Connection;
+use dbus::message::SignalArgs;
+use dbus::Message;
+
+use crate::error::ServiceError;
+use crate::service_manager::{ServiceState, to_service_state};
+use crate::unix::dbus_systemd1::{
+    OrgFreedesktopDBusProperties,
+    OrgFreedesktopDBusPropertiesPropertiesChanged as PropertiesChanged,
+    OrgFreedesktopSystemd1Manager,
+    OrgFreedesktopSystemd1ManagerUnitNew as UnitNew,
+    OrgFreedesktopSystemd1ManagerUnitRemoved as UnitRemoved,
+};
+use crate::unix::services::{from_dbus_error, UnitInfo};
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+const PATH: &str = "/org/freedesktop/systemd1";
+const UNIT_PATH: &str = "/org/freedesktop/systemd1/unit";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+
+/// Provides a state change of a watched unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStateChanged {
+    /// Unit name, e.g. "my-service.service"
+    pub name: String,
+    /// State before the change
+    pub old: ServiceState,
+    /// State after the change
+    pub new: ServiceState,
+}
+
+/// Change received by signal callbacks, processed later by the watcher
+enum Change {
+    /// Unit properties changed, contains unit name, ActiveState and SubState
+    State(String, String, String),
+    /// Unit was loaded by systemd, contains unit name and object path
+    Loaded(String, dbus::Path<'static>),
+    /// Unit was unloaded by systemd, contains unit name
+    Unloaded(String),
+}
+
+/// Watches state changes of specified systemd units
+///
+/// Subscribes to systemd manager signals, and receives *PropertiesChanged* signals of unit objects
+/// and *UnitNew*/*UnitRemoved* signals of the manager. State changes of watched units are returned
+/// by [next_timeout](UnitWatcher::next_timeout), by [watch](UnitWatcher::watch) or by iterating
+/// the watcher.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use cross_platform_service::unix::watcher::UnitWatcher;
+///
+/// let watcher = UnitWatcher::new(vec!["my-service.service"], Duration::from_secs(30)).unwrap();
+///
+/// for event in watcher {
+///     let event = event.unwrap();
+///     println!("{}: {:?} -> {:?}", event.name, event.old, event.new);
+/// }
+/// ```
+pub struct UnitWatcher {
+    connection: Connection,
+    timeout: Duration,
+    states: HashMap<String, ServiceState>,
+    changes: Arc<Mutex<VecDeque<Change>>>,
+}
+
+impl UnitWatcher {
+    /// Connects to systemd over system bus and starts watching specified units
+    ///
+    /// Specified timeout is used for every D-Bus method call.
+    pub fn new(units: Vec<&str>, timeout: Duration) -> Result<UnitWatcher, ServiceError> {
+        let connection = Connection::new_system()
+            .map_err(from_dbus_error)?;
+        let changes = Arc::new(Mutex::new(VecDeque::new()));
+
+        let paths: HashMap<String, String> = units.iter()
+            .map(|it| (unit_path(it), it.to_string()))
+            .collect();
+        let names: HashSet<String> = units.iter()
+            .map(|it| it.to_string())
+            .collect();
+
+        let cb_changes = changes.clone();
+        let rule = PropertiesChanged::match_rule(Some(&DESTINATION.into()), None)
+            .with_namespaced_path(UNIT_PATH)
+            .static_clone();
+
+        connection.add_match(rule, move |signal: PropertiesChanged, _: &Connection, message: &Message| {
+            let name = message.path().and_then(|path| paths.get(&*path));
+
+            if let (Some(name), true) = (name, signal.interface == UNIT_INTERFACE) {
+                if let Some(active_state) = prop_cast::<String>(&signal.changed_properties, "ActiveState") {
+                    let sub_state = prop_cast::<String>(&signal.changed_properties, "SubState")
+                        .cloned()
+                        .unwrap_or_default();
+
+                    cb_changes.lock().unwrap()
+                        .push_back(Change::State(name.clone(), active_state.clone(), sub_state));
+                }
+            }
+
+            true
+        }).map_err(from_dbus_error)?;
+
+        let p = connection.with_proxy(DESTINATION, PATH, timeout);
+
+        let cb_changes = changes.clone();
+        let cb_names = names.clone();
+        p.match_signal(move |signal: UnitNew, _: &Connection, _: &Message| {
+            if cb_names.contains(&signal.id) {
+                cb_changes.lock().unwrap().push_back(Change::Loaded(signal.id, signal.unit));
+            }
+
+            true
+        }).map_err(from_dbus_error)?;
+
+        let cb_changes = changes.clone();
+        p.match_signal(move |signal: UnitRemoved, _: &Connection, _: &Message| {
+            if names.contains(&signal.id) {
+                cb_changes.lock().unwrap().push_back(Change::Unloaded(signal.id));
+            }
+
+            true
+        }).map_err(from_dbus_error)?;
+
+        p.subscribe()
+            .map_err(from_dbus_error)?;
+
+        let list: Vec<UnitInfo> = p.list_units_by_names(units.clone())
+            .map_err(from_dbus_error)?;
+
+        let states = list.iter()
+            .map(|unit| {
+                let state = if unit.2.eq("not-found") {
+                    ServiceState::NotInstalled
+                } else {
+                    to_service_state(unit.3.as_str(), unit.4.as_str())
+                };
+
+                (unit.0.clone(), state)
+            })
+            .collect();
+
+        Ok(UnitWatcher {
+            connection,
+            timeout,
+            states,
+            changes,
+        })
+    }
+
+    /// Returns last known state of specified unit
+    pub fn state(&self, unit_name: &str) -> Option<ServiceState> {
+        self.states.get(unit_name).copied()
+    }
+
+    /// Waits for next state change of watched units
+    ///
+    /// Returns `None` when no state changed before the timeout.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Result<Option<ServiceStateChanged>, ServiceError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let change = self.changes.lock().unwrap().pop_front();
+
+            if let Some(change) = change {
+                let (name, new) = match change {
+                    Change::State(name, active_state, sub_state) => {
+                        let state = to_service_state(active_state.as_str(), sub_state.as_str());
+                        (name, state)
+                    }
+                    Change::Loaded(name, path) => {
+                        let state = self.get_loaded_state(path)?;
+                        (name, state)
+                    }
+                    Change::Unloaded(name) => {
+                        let state = self.get_unloaded_state(name.as_str())?;
+                        (name, state)
+                    }
+                };
+
+                let old = self.states.insert(name.clone(), new)
+                    .unwrap_or(ServiceState::Unknown);
+
+                if old != new {
+                    return Ok(Some(ServiceStateChanged { name, old, new }));
+                }
+
+                continue;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            self.connection.process(deadline - now)
+                .map_err(from_dbus_error)?;
+        }
+    }
+
+    /// Calls specified function for every state change of watched units
+    ///
+    /// Blocks current thread until the function returns `false` or an error occurs.
+    pub fn watch<F>(&mut self, mut f: F) -> Result<(), ServiceError>
+        where F: FnMut(ServiceStateChanged) -> bool {
+        loop {
+            if let Some(event) = self.next_timeout(Duration::from_secs(3600))? {
+                if !f(event) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Gets state of a unit just loaded by systemd
+    fn get_loaded_state(&self, path: dbus::Path<'static>) -> Result<ServiceState, ServiceError> {
+        let p = self.connection.with_proxy(DESTINATION, path, self.timeout);
+
+        let get = |property: &str| -> Result<String, ServiceError> {
+            p.get(UNIT_INTERFACE, property)
+                .map_err(from_dbus_error)
+        };
+
+        if get("LoadState")? == "not-found" {
+            Ok(ServiceState::NotInstalled)
+        } else {
+            Ok(to_service_state(get("ActiveState")?.as_str(), get("SubState")?.as_str()))
+        }
+    }
+
+    /// Gets state of a unit unloaded by systemd, without loading it again
+    fn get_unloaded_state(&self, unit_name: &str) -> Result<ServiceState, ServiceError> {
+        let p = self.connection.with_proxy(DESTINATION, PATH, self.timeout);
+
+        match p.get_unit_file_state(unit_name).map_err(from_dbus_error) {
+            Ok(_) => Ok(ServiceState::Stopped),
+            Err(ServiceError::NotFound(_)) => Ok(ServiceState::NotInstalled),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Iterator for UnitWatcher {
+    type Item = Result<ServiceStateChanged, ServiceError>;
+
+    /// Blocks until next state change of watched units
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_timeout(Duration::from_secs(3600)) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Returns D-Bus object path of specified unit
+///
+/// Characters other than ASCII letters and digits, and a leading digit, are escaped as `_xx` by
+/// their hexadecimal value, same as *sd_bus_path_encode*.
+fn unit_path(unit_name: &str) -> String {
+    let mut path = format!("{}/", UNIT_PATH);
+
+    for (index, byte) in unit_name.bytes().enumerate() {
+        if byte.is_ascii_alphabetic() || (byte.is_ascii_digit() && index > 0) {
+            path.push(byte as char);
+        } else {
+            path.push_str(format!("_{:02x}", byte).as_str());
+        }
+    }
+
+    path
+}
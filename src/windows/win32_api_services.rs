@@ -307,6 +307,37 @@ impl TryFrom<SERVICE_STATUS> for ServiceStatus {
 
 //endregion
 
+//region --- ServiceStatusProcess ---
+
+/// Provides Windows API [SERVICE_STATUS_PROCESS](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/ns-winsvc-service_status_process)
+pub struct ServiceStatusProcess {
+    pub status: ServiceStatus,
+    pub process_id: u32,
+    pub service_flags: u32,
+}
+
+impl TryFrom<SERVICE_STATUS_PROCESS> for ServiceStatusProcess {
+    type Error = Error;
+
+    fn try_from(value: SERVICE_STATUS_PROCESS) -> Result<Self, Self::Error> {
+        Ok(ServiceStatusProcess {
+            status: ServiceStatus::try_from(SERVICE_STATUS {
+                dwServiceType: value.dwServiceType,
+                dwCurrentState: value.dwCurrentState,
+                dwControlsAccepted: value.dwControlsAccepted,
+                dwWin32ExitCode: value.dwWin32ExitCode,
+                dwServiceSpecificExitCode: value.dwServiceSpecificExitCode,
+                dwCheckPoint: value.dwCheckPoint,
+                dwWaitHint: value.dwWaitHint,
+            })?,
+            process_id: value.dwProcessId,
+            service_flags: value.dwServiceFlags,
+        })
+    }
+}
+
+//endregion
+
 /// Calls Windows API [OpenSCManagerW](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-openscmanagerw)
 pub fn open_service_manager() -> Result<ServiceHandle, Error> {
     unsafe {
@@ -481,6 +512,25 @@ pub fn query_service_status(handle: &ServiceHandle) -> Result<ServiceStatus, Err
     }
 }
 
+/// Calls Windows API [QueryServiceStatusEx](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-queryservicestatusex)
+/// with *SC_STATUS_PROCESS_INFO* level
+pub fn query_service_status_process(handle: &ServiceHandle) -> Result<ServiceStatusProcess, Error> {
+    unsafe {
+        let mut status: SERVICE_STATUS_PROCESS = std::mem::zeroed();
+        let mut bytes_needed = 0u32;
+
+        if QueryServiceStatusEx(handle.0,
+                                SC_STATUS_PROCESS_INFO,
+                                &mut status as *mut SERVICE_STATUS_PROCESS as *mut u8,
+                                std::mem::size_of::<SERVICE_STATUS_PROCESS>() as u32,
+                                &mut bytes_needed).as_bool() {
+            ServiceStatusProcess::try_from(status)
+        } else {
+            Err(Error::last_os_error())
+        }
+    }
+}
+
 /// Calls Windows API [DeleteService](https://docs.microsoft.com/en-us/windows/win32/api/winsvc/nf-winsvc-deleteservice)
 pub fn delete_service(handle: &ServiceHandle) -> Result<(), Error> {
     unsafe {
@@ -84,6 +84,27 @@ pub fn get_service_status(service_name: &str) -> Result<ServiceStatus, Error> {
     }
 }
 
+/// Get current status and process ID of specified Windows service
+pub fn get_service_status_process(service_name: &str) -> Result<ServiceStatusProcess, Error> {
+    let sc_handle = open_service_manager()?;
+
+    match open_service(&sc_handle, service_name) {
+        Ok(s_handle) => {
+            let result = query_service_status_process(&s_handle);
+
+            close_service_handle(&s_handle);
+            close_service_handle(&sc_handle);
+
+            result
+        }
+        Err(err) => {
+            close_service_handle(&sc_handle);
+
+            Err(err)
+        }
+    }
+}
+
 /// Install new service to Windows from specified information
 pub fn install_windows_service(info: WindowsServiceInfo) -> Result<(), Error> {
     let sc_handle = open_service_manager()?;